@@ -1,12 +1,17 @@
 use std::fmt;
+use ff_structure::PairTable;
 
 use crate::NearestNeighborLoop;
 use crate::LoopDecomposition;
 use crate::PairTypeRNA;
 use crate::Base;
+use crate::NucleotideVec;
 
 pub const K0: f64 = 273.15;
 
+/// Free energies are integers in dcal/mol, divide by this for kcal/mol.
+pub const DCAL_PER_KCAL: f64 = 100.;
+
 #[derive(Debug)]
 pub enum EnergyError {
     HairpinTooSmall { size: usize, min: usize },
@@ -48,5 +53,14 @@ pub trait EnergyModel: Send + Sync {
         sequence: &[Base],
         nn_loop: &NearestNeighborLoop,
     ) -> Result<i32, EnergyError>;
+
+    /// Free energy (in dcal/mol) of a single-stranded structure, 
+    /// summed over all loops. Panics for invalid sequences or structures.
+    fn eval_structure(&self, seq: &str, pt: &PairTable) -> i32 where Self: Sized {
+        let sequence = NucleotideVec::try_from(seq)
+            .unwrap_or_else(|e| panic!("Invalid sequence {}: {}.", seq, e));
+        self.energy_of_structure(&sequence, pt)
+            .unwrap_or_else(|e| panic!("Energy evaluation error: {}.", e))
+    }
 }
 
@@ -1,3 +1,4 @@
+use std::path::Path;
 use log::info;
 use colored::*; 
 use paste::paste;
@@ -11,6 +12,20 @@ use crate::LoopDecomposition;
 use crate::NearestNeighborLoop;
 use crate::K0;
 
+/// Treatment of dangling ends and terminal mismatches 
+/// in multibranch and exterior loops.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DangleMode {
+    /// No dangling end contributions (-d0).
+    None,
+    /// Only unpaired neighbors contribute (-d1). 
+    /// (Does not resolve neighbors shared by two stems.)
+    Unpaired,
+    /// Neighbors contribute regardless of their pairing state (-d2).
+    #[default]
+    Always,
+}
+
 /// The union of different parameterizations,
 /// may contain redundant fallback parameters.
 pub struct ViennaRNA {
@@ -27,6 +42,13 @@ pub struct ViennaRNA {
     /// An extended parameter table for stacks. 
     stack: ExtendedStackParams,
 
+    /// Salt correction added to every helix stack. 
+    /// (Zero at the standard conditions of 1M Na+.)
+    salt_stack: i32,
+
+    /// Dangling end model for multibranch and exterior loops.
+    dangles: DangleMode,
+
     mismatch_hairpin: MismatchParams,
     mismatch_interior: MismatchParams,
     mismatch_interior_1n: MismatchParams,
@@ -102,6 +124,8 @@ impl ViennaRNA {
             temperature: 37.0,
 
             stack: *params.stack,
+            salt_stack: 0,
+            dangles: DangleMode::default(),
             mismatch_hairpin: *params.mismatch_hairpin,
             mismatch_interior: *params.mismatch_interior,
             mismatch_interior_1n: *params.mismatch_interior_1n,
@@ -146,6 +170,8 @@ impl ViennaRNA {
                 temperature: 37.0,
 
                 stack: *params.stack_en37,
+                salt_stack: 0,
+                dangles: DangleMode::default(),
                 mismatch_hairpin: *params.mismatch_hairpin_en37,
                 mismatch_interior: *params.mismatch_interior_en37,
                 mismatch_interior_1n: *params.mismatch_interior_1n_en37,
@@ -188,6 +214,8 @@ impl ViennaRNA {
                 temperature: celsius,
 
                 stack: rescale_params!(stack, params, scale),
+                salt_stack: 0,
+                dangles: DangleMode::default(),
                 mismatch_hairpin: rescale_params!(mismatch_hairpin, params, scale),
                 mismatch_interior: rescale_params!(mismatch_interior, params, scale),
                 mismatch_interior_1n: rescale_params!(mismatch_interior_1n, params, scale),
@@ -225,6 +253,79 @@ impl ViennaRNA {
         }
     }
 
+    /// Initializes the default model at 37°C and overrides all tables
+    /// given in a ViennaRNA parameter file. Pseudouridine stacks are
+    /// kept from the default parameters. The file only provides free
+    /// energies at 37°C, any other temperature is an error.
+    pub fn from_param_file(
+        path: impl AsRef<Path>,
+        celsius: f64,
+    ) -> Result<Self, ParamFileError> {
+        if celsius != 37.0 {
+            return Err(ParamFileError::Temperature(celsius));
+        }
+        let params = ParamFile::from_file(path)?;
+        let mut model = Self::default();
+        if let Some(stack) = params.stack {
+            for (i, row) in stack.iter().enumerate() {
+                for (j, &en) in row.iter().enumerate() {
+                    model.stack[i][j] = (en != i32::MAX).then_some(en);
+                }
+            }
+        }
+        if let Some(hairpin) = params.hairpin {
+            model.hairpin = hairpin;
+        }
+        if let Some(bulge) = params.bulge {
+            model.bulge = bulge;
+        }
+        if let Some(interior) = params.interior {
+            model.interior = interior;
+        }
+        Ok(model)
+    }
+
+    /// Applies a salt correction to helix stabilities for the given
+    /// molar concentrations of Na+ and Mg2+.
+    ///
+    /// Mg2+ is converted into an equivalent Na+ concentration
+    /// (Na + 3.3 * sqrt(Mg)), and every stack is corrected by the 
+    /// entropic term -T * 0.368 * ln(Na_eq) (SantaLucia, 1998).
+    /// The parameters are measured at 1M Na+, so that is a no-op.
+    ///
+    /// Panics if a concentration is negative, or if both are zero.
+    pub fn with_salt(mut self, na: f64, mg: f64) -> Self {
+        assert!(na >= 0.0 && mg >= 0.0, 
+            "Salt concentrations must be non-negative (Na+: {}, Mg2+: {})", na, mg);
+        let na_eq = na + 3.3 * mg.sqrt();
+        assert!(na_eq > 0.0, "Salt correction requires a non-zero Na+ or Mg2+ concentration");
+        let kelvin = self.temperature + K0;
+        // cal/mol -> dcal/mol
+        self.salt_stack = (-kelvin * 0.368 * na_eq.ln() / 10.).round() as i32;
+        self
+    }
+
+    /// Selects the dangling end model for multibranch and exterior loops.
+    pub fn with_dangles(mut self, dangles: DangleMode) -> Self {
+        self.dangles = dangles;
+        self
+    }
+
+    /// Filters the 5' and 3' neighbors of a stem according to the 
+    /// dangle mode, given whether they are unpaired.
+    fn dangle_neighbors<'a>(&self, 
+        d5: Option<&'a Base>, 
+        d3: Option<&'a Base>,
+        d5_unpaired: bool,
+        d3_unpaired: bool,
+    ) -> (Option<&'a Base>, Option<&'a Base>) {
+        match self.dangles {
+            DangleMode::None => (None, None),
+            DangleMode::Unpaired => (d5.filter(|_| d5_unpaired), d3.filter(|_| d3_unpaired)),
+            DangleMode::Always => (d5, d3),
+        }
+    }
+
     fn hairpin_bonus(&self, seq: &[Base]) -> Option<i32> {
         let table = match seq.len() {
             5 => &self.triloops,
@@ -298,11 +399,13 @@ impl ViennaRNA {
             (2, 2) => {
                 self.stack[outer as usize][inner as usize]
                     .ok_or(EnergyError::UnsupportedStacking { outer, inner })?
+                    + self.salt_stack
             },
             (3, 2) | (2, 3) => { //NOTE: SpecialC if C adjacent to paired C missing!
                 self.bulge[1] + 
                     self.stack[outer as usize][inner as usize]
                     .ok_or(EnergyError::UnsupportedStacking { outer, inner })?
+                    + self.salt_stack
             },
             (3, 3) => 
                 self.int11[fb_outer as usize][fb_inner as usize]
@@ -424,6 +527,9 @@ impl ViennaRNA {
             let d5 = segments.get(i)
                 .and_then(|seg| seg.len().checked_sub(2).and_then(|d| seg.get(d)));
             let d3 = segments.get(j).and_then(|seg| seg.get(1));
+            // Every segment is enclosed by paired bases.
+            let (d5, d3) = self.dangle_neighbors(d5, d3, 
+                segments[i].len() > 2, segments[j].len() > 2);
 
             let fb_pair = PairTypeRNA::from_fallback((*segments[i].last().unwrap(), segments[j][0]));
             //NOTE: This does not take the minimum over all options, it always
@@ -474,6 +580,10 @@ impl ViennaRNA {
             let d5 = segments.get(i)
                 .and_then(|seg| seg.len().checked_sub(2).and_then(|d| seg.get(d)));
             let d3 = segments.get(j).and_then(|seg| seg.get(1));
+            // Only the first and last segments start/end with an unpaired base.
+            let (d5, d3) = self.dangle_neighbors(d5, d3, 
+                segments[i].len() > 2 || i == 0, 
+                segments[j].len() > 2 || j == n);
 
             let fb_pair = PairTypeRNA::from_fallback((*segments[i].last().unwrap(), segments[j][0]));
             //NOTE: This does not take the minimum over all options, it always
@@ -873,6 +983,56 @@ mod tests {
         assert_eos!(model, seq, dbr, e37);
     }
  
+    #[test]
+    fn test_eval_structure() {
+        let model = ViennaRNA::default();
+        let pt = PairTable::try_from("(((((...)))))").unwrap();
+        assert_eq!(model.eval_structure("ACGUUAAAGACGU", &pt), -170);
+        let pt = PairTable::try_from("(....)").unwrap();
+        assert_eq!(model.eval_structure("GAAAAC", &pt), 450);
+    }
+ 
+    #[test]
+    fn test_from_param_file() {
+        let default = ViennaRNA::default();
+        // Vienna order: CG, GC, GU, UG, AU, UA, NS
+        let order = [2, 3, 4, 5, 0, 1];
+        let mut content = String::from("## RNAfold parameter file v2.0\n\n# stack\n");
+        for &i in &order {
+            for &j in &order {
+                content += &format!("{:>6}", 2 * default.stack[i][j].unwrap());
+            }
+            content += "   INF\n";
+        }
+        content += &"   INF".repeat(7);
+        content += "\n\n# END\n";
+        let path = std::env::temp_dir().join("ff_energy_scaled_stacks.par");
+        std::fs::write(&path, content).unwrap();
+
+        let scaled = ViennaRNA::from_param_file(&path, 37.0).unwrap();
+        let seq = NucleotideVec::try_from("GGGGAAACCCCA").unwrap();
+        let stacked = PairTable::try_from("((((...)))).").unwrap();
+        for (k, l) in [(1, 9), (2, 8), (3, 7)] {
+            let nn_loop = NearestNeighborLoop::Interior { closing: (k - 1, l + 1), inner: (k, l) };
+            assert_eq!(
+                scaled.energy_of_loop(&seq, &nn_loop).unwrap(),
+                2 * default.energy_of_loop(&seq, &nn_loop).unwrap()
+            );
+        }
+        let opened = PairTable::try_from(".(((...)))..").unwrap();
+        let delta_e = |m: &ViennaRNA| {
+            m.energy_of_structure(&seq, &stacked).unwrap()
+                - m.energy_of_structure(&seq, &opened).unwrap()
+        };
+        assert!(delta_e(&scaled) < delta_e(&default));
+
+        assert!(matches!(
+            ViennaRNA::from_param_file(&path, 25.0),
+            Err(ParamFileError::Temperature(t)) if t == 25.0
+        ));
+        assert!(ViennaRNA::from_param_file("/nonexistent/rna.par", 37.0).is_err());
+    }
+ 
     macro_rules! assert_meos {
         ($model:expr, $seq:expr, $dbr:expr, $val:expr) => {
             assert_eq!(
@@ -919,5 +1079,67 @@ mod tests {
         assert_meos!(model, seq, dbr, e37);
     }
 
-}
+    #[test]
+    fn test_salt_correction() {
+        let seq = NucleotideVec::try_from("GGCGC+GCGCC").unwrap();
+        let pt = MultiPairTable::try_from("(((((+)))))").unwrap();
+
+        let standard = ViennaRNA::default();
+        let neutral = ViennaRNA::default().with_salt(1.0, 0.0);
+        let low_salt = ViennaRNA::default().with_salt(0.1, 0.0);
+        let magnesium = ViennaRNA::default().with_salt(0.1, 0.01);
+
+        let e_std = standard.energy_of_structure(&seq, &pt).unwrap();
+        let e_1m = neutral.energy_of_structure(&seq, &pt).unwrap();
+        let e_01m = low_salt.energy_of_structure(&seq, &pt).unwrap();
+        let e_mg = magnesium.energy_of_structure(&seq, &pt).unwrap();
+
+        assert_eq!(e_std, e_1m);
+        // Four stacks, each destabilized by ~0.26 kcal/mol.
+        assert_eq!(e_01m - e_1m, 4 * 26);
+        assert!(e_1m < e_mg && e_mg < e_01m);
+
+        // Sodium-free buffers are fine, as long as there is magnesium.
+        let mg_only = ViennaRNA::default().with_salt(0.0, 0.01);
+        assert!(mg_only.energy_of_structure(&seq, &pt).unwrap() > e_mg);
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero Na+ or Mg2+")]
+    fn test_salt_correction_without_salt() {
+        let _ = ViennaRNA::default().with_salt(0.0, 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be non-negative")]
+    fn test_salt_correction_negative() {
+        let _ = ViennaRNA::default().with_salt(-1.0, 0.0);
+    }
+
+    #[test]
+    fn test_dangle_modes() {
+        let seq = NucleotideVec::try_from("AGGGGAAACCCCA").unwrap();
+        let open = PairTable::try_from("..(((...)))..").unwrap();
+        let closed = PairTable::try_from(".((((...)))).").unwrap();
+
+        let delta_e = |model: &ViennaRNA| {
+            model.energy_of_structure(&seq, &closed).unwrap()
+                - model.energy_of_structure(&seq, &open).unwrap()
+        };
 
+        let d0 = ViennaRNA::default().with_dangles(DangleMode::None);
+        let d1 = ViennaRNA::default().with_dangles(DangleMode::Unpaired);
+        let d2 = ViennaRNA::default();
+        assert_eq!(d2.dangles, DangleMode::Always);
+        assert_ne!(delta_e(&d0), delta_e(&d2));
+        // All exterior neighbors are unpaired.
+        assert_eq!(delta_e(&d1), delta_e(&d2));
+
+        let seg1 = &NucleotideVec::try_from("AUG").unwrap();
+        let seg2 = &NucleotideVec::try_from("CG").unwrap();
+        let seg3 = &NucleotideVec::try_from("CUG").unwrap();
+        assert_eq!(d0.exterior(&[seg1, seg2, seg3]).unwrap(), 0);
+        assert!(d1.exterior(&[seg1, seg2, seg3]).unwrap() 
+            > d2.exterior(&[seg1, seg2, seg3]).unwrap());
+    }
+}
@@ -1,5 +1,6 @@
 mod energyparam;
 mod parameterset;
+mod param_file;
 pub mod rna;
 pub mod rna_turner_2004;
 pub mod rna_andronescu_2007;
@@ -7,6 +8,7 @@ pub mod dna_mathews_2004;
 
 pub use energyparam::*;
 pub use parameterset::*;
+pub use param_file::*;
 pub use rna::RNA_EXTENDED;
 pub use rna_turner_2004::RNA_TURNER_2004;
 pub use rna_andronescu_2007::AndronescuParams;
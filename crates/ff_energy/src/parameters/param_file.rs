@@ -0,0 +1,239 @@
+//! Reading ViennaRNA parameter files (RNAfold parameter file v2.0).
+//!
+//! Only a subset of the sections is supported, namely the stacking
+//! free energies and the loop initiation free energies. All other
+//! sections are skipped with a warning.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use log::warn;
+
+use crate::parameters::parameterset::LoopParams;
+use crate::parameters::parameterset::StackParams;
+use crate::parameters::parameterset::MAX_LOOP;
+use crate::parameters::parameterset::P;
+
+/// The Vienna pair order is CG, GC, GU, UG, AU, UA, NS,
+/// this maps it onto AU, UA, CG, GC, GU, UG.
+const VRNA_PAIR_ORDER: [usize; P] = [2, 3, 4, 5, 0, 1];
+
+/// Number of pair types in Vienna tables (including non-standard).
+const VRNA_PAIRS: usize = 7;
+
+#[derive(Debug)]
+pub enum ParamFileError {
+    Io(std::io::Error),
+    Parse { line: usize, msg: String },
+    SectionSize { section: String, expected: usize, found: usize },
+    Temperature(f64),
+}
+
+impl fmt::Display for ParamFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamFileError::Io(e) => write!(f, "Cannot read parameter file: {}", e),
+            ParamFileError::Parse { line, msg } => {
+                write!(f, "Parameter file line {}: {}", line, msg)
+            }
+            ParamFileError::SectionSize { section, expected, found } => {
+                write!(f, "Section '{}' expects {} values, found {}", section, expected, found)
+            }
+            ParamFileError::Temperature(celsius) => {
+                write!(f, "Parameter files are only supported at 37°C, not {}°C", celsius)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParamFileError {}
+
+impl From<std::io::Error> for ParamFileError {
+    fn from(e: std::io::Error) -> Self {
+        ParamFileError::Io(e)
+    }
+}
+
+/// Free energies (dcal/mol at 37°C) that override the built-in tables.
+/// Missing (INF) entries are represented as `i32::MAX`.
+#[derive(Debug, Default, Clone)]
+pub struct ParamFile {
+    pub stack: Option<StackParams>,
+    pub hairpin: Option<LoopParams>,
+    pub bulge: Option<LoopParams>,
+    pub interior: Option<LoopParams>,
+}
+
+impl ParamFile {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ParamFileError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, ParamFileError> {
+        let mut params = ParamFile::default();
+        let mut section: Option<(String, Vec<i32>)> = None;
+        let mut in_comment = false;
+
+        for (l, line) in content.lines().enumerate() {
+            let line = strip_comments(line, &mut in_comment);
+            let line = line.trim();
+            if line.starts_with("##") || line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('#') {
+                if let Some((name, values)) = section.take() {
+                    params.insert(&name, values)?;
+                }
+                let name = name.trim();
+                if name != "END" {
+                    section = Some((name.to_string(), Vec::new()));
+                }
+                continue;
+            }
+            let Some((_, values)) = section.as_mut() else {
+                return Err(ParamFileError::Parse {
+                    line: l + 1,
+                    msg: format!("Values outside of a section: '{}'", line),
+                });
+            };
+            for token in line.split_whitespace() {
+                values.push(match token {
+                    "INF" => i32::MAX,
+                    _ => token.parse().map_err(|_| ParamFileError::Parse {
+                        line: l + 1,
+                        msg: format!("Invalid value '{}'", token),
+                    })?,
+                });
+            }
+        }
+        if let Some((name, values)) = section.take() {
+            params.insert(&name, values)?;
+        }
+        Ok(params)
+    }
+
+    fn insert(&mut self, section: &str, values: Vec<i32>) -> Result<(), ParamFileError> {
+        let expect = |n: usize| {
+            if values.len() == n {
+                Ok(())
+            } else {
+                Err(ParamFileError::SectionSize {
+                    section: section.to_string(),
+                    expected: n,
+                    found: values.len(),
+                })
+            }
+        };
+        match section {
+            "stack" => {
+                expect(VRNA_PAIRS * VRNA_PAIRS)?;
+                let mut stack = [[i32::MAX; P]; P];
+                for (vi, &i) in VRNA_PAIR_ORDER.iter().enumerate() {
+                    for (vj, &j) in VRNA_PAIR_ORDER.iter().enumerate() {
+                        stack[i][j] = values[vi * VRNA_PAIRS + vj];
+                    }
+                }
+                self.stack = Some(stack);
+            }
+            "hairpin" | "bulge" | "interior" => {
+                expect(MAX_LOOP + 1)?;
+                let mut table = [i32::MAX; MAX_LOOP + 1];
+                table.copy_from_slice(&values);
+                match section {
+                    "hairpin" => self.hairpin = Some(table),
+                    "bulge" => self.bulge = Some(table),
+                    _ => self.interior = Some(table),
+                }
+            }
+            _ => warn!("Skipping unsupported parameter section '{}'.", section),
+        }
+        Ok(())
+    }
+}
+
+/// Removes C-style comments, which may span multiple lines.
+fn strip_comments(line: &str, in_comment: &mut bool) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    loop {
+        if *in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    *in_comment = false;
+                    rest = &rest[end + 2..];
+                }
+                None => return out,
+            }
+        } else {
+            match rest.find("/*") {
+                Some(start) => {
+                    out.push_str(&rest[..start]);
+                    *in_comment = true;
+                    rest = &rest[start + 2..];
+                }
+                None => {
+                    out.push_str(rest);
+                    return out;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sections() {
+        let content = "## RNAfold parameter file v2.0\n\
+            \n\
+            # stack\n\
+            /*  CG     GC     GU     UG     AU     UA     @  */\n\
+            -240   -330   -210   -140   -210   -210   -140  /* CG */\n\
+            -330   -340   -250   -150   -220   -240   -150  /* GC */\n\
+            -210   -250    130    -50   -140   -130    130  /* GU */\n\
+            -140   -150    -50     30    -60   -100     30  /* UG */\n\
+            -210   -220   -140    -60   -110    -90    -60  /* AU */\n\
+            -210   -240   -130   -100    -90   -130    -90  /* UA */\n\
+            -140   -150    130     30    -60    -90    130  /* @ */\n\
+            \n\
+            # mismatch_hairpin\n\
+            0 0 0\n\
+            \n\
+            # hairpin\n\
+            INF INF INF 540 560 570 540 600 550 640 650\n\
+            660 670 678 686 694 701 707 713 719 725\n\
+            730 735 740 744 749 753 757 761 765 769\n\
+            \n\
+            # END\n";
+        let params = ParamFile::parse(content).unwrap();
+        let stack = params.stack.unwrap();
+        assert_eq!(stack[2][2], -240); // CG-CG
+        assert_eq!(stack[2][3], -330); // CG-GC
+        assert_eq!(stack[0][1], -90);  // AU-UA
+        assert_eq!(stack[4][4], 130);  // GU-GU
+        let hairpin = params.hairpin.unwrap();
+        assert_eq!(hairpin[0], i32::MAX);
+        assert_eq!(hairpin[3], 540);
+        assert_eq!(hairpin[30], 769);
+        assert!(params.bulge.is_none());
+        assert!(params.interior.is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = ParamFile::parse("# stack\n-240 -330 abc\n").unwrap_err();
+        assert!(matches!(err, ParamFileError::Parse { line: 2, .. }));
+
+        let err = ParamFile::parse("# hairpin\nINF INF INF 540\n# END\n").unwrap_err();
+        assert!(matches!(err, ParamFileError::SectionSize { expected: 31, found: 4, .. }));
+
+        let err = ParamFile::parse("-240\n").unwrap_err();
+        assert!(matches!(err, ParamFileError::Parse { line: 1, .. }));
+
+        let err = ParamFile::from_file("/nonexistent/rna.par").unwrap_err();
+        assert!(matches!(err, ParamFileError::Io(_)));
+    }
+}
@@ -0,0 +1,211 @@
+use std::io;
+use std::sync::Arc;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use ff_structure::PairTable;
+use ff_energy::EnergyModel;
+
+use crate::SSA;
+use crate::Event;
+use crate::Walker;
+use crate::RateModel;
+use crate::timeline::Timeline;
+
+/// Simulate one trajectory and assign its structures to the timepoints of
+/// the timeline. If the trajectory enters one of the `absorbing` macrostates, 
+/// the simulation stops and the structure is assigned to all remaining
+/// timepoints. Returns false if the simulation was stopped early.
+pub fn simulate_timeline<W, K, E, R>(
+    simulator: &mut SSA<W, K>,
+    rng: &mut R,
+    timeline: &mut Timeline<E>,
+    absorbing: &[usize],
+) -> bool
+where
+    W: Walker,
+    K: RateModel,
+    E: EnergyModel,
+    R: Rng + ?Sized,
+{
+    let t_max = match timeline.points.last() {
+        Some(tp) => tp.time,
+        None => return true,
+    };
+    simulator.simulate(rng, t_max, timeline_callback(timeline, absorbing))
+}
+
+/// Same as `simulate_timeline`, but returns every applied move of the 
+/// trajectory (see `SSA::simulate_recording`).
+pub fn simulate_timeline_recording<W, K, E, R>(
+    simulator: &mut SSA<W, K>,
+    rng: &mut R,
+    timeline: &mut Timeline<E>,
+    absorbing: &[usize],
+) -> Vec<Event>
+where
+    W: Walker,
+    K: RateModel,
+    E: EnergyModel,
+    R: Rng + ?Sized,
+{
+    let t_max = match timeline.points.last() {
+        Some(tp) => tp.time,
+        None => return Vec::new(),
+    };
+    simulator.simulate_recording(rng, t_max, timeline_callback(timeline, absorbing))
+}
+
+/// The simulation callback that assigns the current structure to the 
+/// timepoints of the timeline.
+fn timeline_callback<'a, W, E>(
+    timeline: &'a mut Timeline<E>,
+    absorbing: &'a [usize],
+) -> impl FnMut(f64, f64, f64, &W) -> bool + 'a
+where
+    W: Walker,
+    E: EnergyModel,
+{
+    let mut t_idx = 0;
+    move |t, tinc, _, w| {
+        let structure = w.current_structure();
+        if !absorbing.is_empty() && absorbing.contains(&timeline.registry.classify(&structure)) {
+            timeline.assign_remaining(t_idx, &structure);
+            return false;
+        }
+        while t_idx < timeline.points.len() && t + tinc >= timeline.points[t_idx].time {
+            timeline.assign_structure(t_idx, &structure);
+            t_idx += 1;
+        }
+        true
+    }
+}
+
+/// Receives the index and the moves of every finished trajectory.
+pub type EventSink = Arc<dyn Fn(u64, &[Event]) -> io::Result<()> + Send + Sync>;
+
+/// Optional behavior of `run_ensemble_with`.
+#[derive(Clone, Default)]
+pub struct EnsembleHooks {
+    /// Macrostate indices that stop a trajectory, see `simulate_timeline`.
+    pub absorbing: Vec<usize>,
+    /// Called with the index and all moves of every finished trajectory.
+    pub events: Option<EventSink>,
+    /// Called after every finished trajectory.
+    pub progress: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+/// Simulate `n` trajectories in parallel, starting from the walker, and 
+/// merge them into the `master` timeline (which may already contain data).
+///
+/// With a seed, trajectory i uses `StdRng::seed_from_u64(seed + i)`, 
+/// so the result is reproducible.
+pub fn run_ensemble<W, K, E>(
+    walker: &W,
+    rmodel: &K,
+    master: Timeline<E>,
+    n: usize,
+    seed: Option<u64>,
+) -> Timeline<E>
+where
+    W: Walker + Clone + Send + Sync,
+    K: RateModel + Clone,
+    E: EnergyModel,
+{
+    run_ensemble_with(walker, rmodel, master, n, seed, &EnsembleHooks::default())
+        .expect("Without an event sink, only a reset can fail.")
+}
+
+/// Same as `run_ensemble`, with absorbing macrostates, an event sink and 
+/// a progress hook. Returns the first error of the walker reset or the 
+/// event sink.
+pub fn run_ensemble_with<W, K, E>(
+    walker: &W,
+    rmodel: &K,
+    mut master: Timeline<E>,
+    n: usize,
+    seed: Option<u64>,
+    hooks: &EnsembleHooks,
+) -> Result<Timeline<E>, String>
+where
+    W: Walker + Clone + Send + Sync,
+    K: RateModel + Clone,
+    E: EnergyModel,
+{
+    let initial = PairTable::try_from(&walker.current_structure())
+        .map_err(|e| e.to_string())?;
+    let timelines: Vec<_> = (0..n as u64)
+        .into_par_iter()
+        .map_init(
+            || SSA::from((walker.clone(), rmodel.clone())), // one simulator per thread
+            |simulator, idx| {
+                let mut rng = match seed {
+                    Some(s) => StdRng::seed_from_u64(s.wrapping_add(idx)),
+                    None => StdRng::from_os_rng(),
+                };
+                let mut timeline = master.empty_like();
+                simulator.reset(&initial)?;
+                if let Some(sink) = &hooks.events {
+                    let trajectory = simulate_timeline_recording(simulator, &mut rng, &mut timeline, &hooks.absorbing);
+                    sink(idx, &trajectory).map_err(|e| e.to_string())?;
+                } else {
+                    simulate_timeline(simulator, &mut rng, &mut timeline, &hooks.absorbing);
+                }
+                if let Some(progress) = &hooks.progress {
+                    progress();
+                }
+                Ok(timeline)
+            },
+        )
+        .collect::<Result<_, String>>()?;
+
+    for timeline in timelines {
+        master.merge(timeline);
+    }
+    Ok(master)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff_structure::DotBracketVec;
+    use ff_energy::NucleotideVec;
+    use ff_energy::ViennaRNA;
+    use ff_energy::DCAL_PER_KCAL;
+    use crate::Arrhenius;
+    use crate::LoopNeighbors;
+    use crate::MacrostateRegistry;
+    use crate::shift_policy::NoShift;
+    use crate::enum_neighbors::landscape;
+
+    #[test]
+    fn test_run_ensemble_equilibrium() {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let pt = PairTable::try_from(".........").unwrap();
+        let emodel = Arc::new(ViennaRNA::default());
+        let rmodel = Arrhenius::new(emodel.temperature(), 1e5, None, None);
+        let mut registry = MacrostateRegistry::from((seq.clone(), Arc::clone(&emodel)));
+        registry.insert_from_reader(std::io::Cursor::new(b">hairpin\nGGGAAACCC\n(((...)))\n"), "test").unwrap();
+        let registry = Arc::new(registry);
+        let walker = LoopNeighbors::try_from((seq.clone(), &pt, Arc::clone(&emodel), NoShift)).unwrap();
+
+        // The Boltzmann probability of the hairpin.
+        let (states, truncated) = landscape(&pt, seq, Arc::clone(&emodel), 1000).unwrap();
+        assert!(!truncated);
+        let kt = crate::KB * (emodel.temperature() + crate::K0);
+        let weight = |e: i32| (-(e as f64) / DCAL_PER_KCAL / kt).exp();
+        let z: f64 = states.iter().map(|&(_, e)| weight(e)).sum();
+        let hairpin = DotBracketVec::try_from("(((...)))").unwrap();
+        let p_eq = states.iter().find(|(s, _)| *s == hairpin).map(|&(_, e)| weight(e) / z).unwrap();
+
+        let times = [0.0, 0.1];
+        let small = run_ensemble(&walker, &rmodel, Timeline::new(&times, Arc::clone(&registry)), 100, Some(1));
+        let large = run_ensemble(&walker, &rmodel, Timeline::new(&times, Arc::clone(&registry)), 400, Some(1));
+
+        let (tp_small, tp_large) = (small.point(1), large.point(1));
+        assert_eq!((tp_small.counter, tp_large.counter), (100, 400));
+        assert!(tp_large.stderr(1) < tp_small.stderr(1));
+        assert!((tp_large.occupancy(1) - p_eq).abs() < 4.0 * tp_large.stderr(1).max(0.01));
+    }
+}
@@ -1,20 +1,109 @@
+use std::sync::Arc;
 use log::info;
 use rustc_hash::FxHashSet;
 
 use ff_structure::DotBracket;
 use ff_structure::DotBracketVec;
+use ff_structure::PairTable;
+use ff_structure::Pair;
+use ff_structure::NAIDX;
+use ff_structure::LoopInfo;
+use ff_structure::LoopTable as StructureLoopTable;
 use ff_energy::EnergyModel;
+use ff_energy::NucleotideVec;
+use ff_energy::DCAL_PER_KCAL;
 
 use crate::Move;
 use crate::Walker;
 use crate::LoopNeighbors;
+use crate::MacrostateRegistry;
+use crate::{K0, KB};
 use crate::shift_policy::ShiftPolicy;
+use crate::shift_policy::NoShift;
 
 pub trait ApplyMove {
     fn apply_move(&mut self, mv: &Move);
     fn undo_move(&mut self, mv: &Move) {
         self.apply_move(&mv.inverse());
     }
+
+    /// Validate a shift of the existing `pair` to `new_partner` and return
+    /// the resulting pair. The end of `pair` closer to `new_partner` moves
+    /// (the 5' end on ties), and `new_partner` has to be an unpaired
+    /// position in one of the two loops delimited by `pair`.
+    fn try_shift(&self, pair: Pair, new_partner: NAIDX) -> Result<Pair, String>;
+
+    /// Apply a shift validated by `try_shift`, returns the new pair.
+    fn apply_shift(&mut self, pair: Pair, new_partner: NAIDX) -> Result<Pair, String> {
+        let new_pair = self.try_shift(pair, new_partner)?;
+        let mv = if new_pair.i() == pair.i() || new_pair.j() == pair.i() {
+            Move::ShiftIK { i: pair.i(), j: pair.j(), k: new_partner }
+        } else {
+            Move::ShiftJK { i: pair.i(), j: pair.j(), k: new_partner }
+        };
+        self.apply_move(&mv);
+        Ok(new_pair)
+    }
+}
+
+impl ApplyMove for PairTable {
+    fn apply_move(&mut self, mv: &Move) {
+        match *mv {
+            Move::Add { i, j } => {
+                self[i] = Some(j);
+                self[j] = Some(i);
+            }
+            Move::Del { i, j } => {
+                self[i] = None;
+                self[j] = None;
+            }
+            Move::ShiftIK { i, j, k } => {
+                self[j] = None;
+                self[i] = Some(k);
+                self[k] = Some(i);
+            }
+            Move::ShiftJK { i, j, k } => {
+                self[i] = None;
+                self[j] = Some(k);
+                self[k] = Some(j);
+            }
+            Move::ShiftIKLJ { i, j, k, l } => {
+                self[i] = Some(k);
+                self[k] = Some(i);
+                self[l] = Some(j);
+                self[j] = Some(l);
+            } 
+            Move::ShiftILJK { i, j, k, l } => {
+                self[i] = Some(l);
+                self[l] = Some(i);
+                self[j] = Some(k);
+                self[k] = Some(j);
+            }
+        }
+    }
+
+    fn try_shift(&self, pair: Pair, k: NAIDX) -> Result<Pair, String> {
+        let (i, j) = (pair.i(), pair.j());
+        if (k as usize) >= self.len() {
+            return Err(format!("Position {} is out of range.", k));
+        }
+        if self[i] != Some(j) {
+            return Err(format!("({}, {}) is not a base-pair.", i, j));
+        }
+        if self[k].is_some() {
+            return Err(format!("Position {} is not unpaired.", k));
+        }
+        let lt = StructureLoopTable::from(self);
+        let (LoopInfo::Paired { o, i: inner }, LoopInfo::Unpaired { l }) = (lt[i as usize], lt[k as usize]) else {
+            unreachable!("checked above");
+        };
+        if l != o && l != inner {
+            return Err(format!("Position {} is not in a loop delimited by ({}, {}).", k, i, j));
+        }
+        // The end closer to k moves, the other one stays.
+        let stay = if i.abs_diff(k) <= j.abs_diff(k) { j } else { i };
+        Ok(Pair::new(stay.min(k), stay.max(k)))
+    }
 }
 
 impl ApplyMove for DotBracketVec {
@@ -62,6 +151,12 @@ impl ApplyMove for DotBracketVec {
             }
         }
     }
+
+    fn try_shift(&self, pair: Pair, new_partner: NAIDX) -> Result<Pair, String> {
+        PairTable::try_from(self)
+            .map_err(|e| e.to_string())?
+            .try_shift(pair, new_partner)
+    }
 }
 
 
@@ -83,6 +178,19 @@ impl<E: EnergyModel, P: ShiftPolicy> LoopNeighbors<E, P> {
         maxsteps: usize,
         mut callback: F,
     ) where F: FnMut(&DotBracketVec, i32) {
+        self.generate_neighbors_until(maxdelta, maxsteps, |db, en| {
+            callback(db, en);
+            true
+        });
+    }
+
+    /// Like `generate_neighbors`, but stops as soon as the callback returns
+    /// false. Returns false if the enumeration was stopped early.
+    pub fn generate_neighbors_until<F>(&mut self, 
+        maxdelta: i32, 
+        maxsteps: usize,
+        mut callback: F,
+    ) -> bool where F: FnMut(&DotBracketVec, i32) -> bool {
         let root = self.current_structure();
         let mut path: Vec<Move> = Vec::new();
 
@@ -108,7 +216,9 @@ impl<E: EnergyModel, P: ShiftPolicy> LoopNeighbors<E, P> {
                 path.push(bp_move);
             }
             debug_assert_eq!(self.current_structure(), db);
-            callback(&db, self.current_energy());
+            if !callback(&db, self.current_energy()) {
+                return false;
+            }
 
             if path.len() == maxsteps {
                 info!("Reached maxsteps during neighbor generation.");
@@ -131,7 +241,236 @@ impl<E: EnergyModel, P: ShiftPolicy> LoopNeighbors<E, P> {
                 db.undo_move(&bp_move);
             }
         }
+        true
+    }
+}
+
+/// All structures reachable from `start` by base-pair additions and
+/// deletions, sorted by free energy (ascending). 
+///
+/// At most `max_states` structures are enumerated; the returned flag is
+/// true if the enumeration was truncated at that cap. Without truncation,
+/// this is the complete secondary structure space of the sequence.
+pub fn landscape<E: EnergyModel>(
+    start: &PairTable,
+    sequence: NucleotideVec,
+    model: Arc<E>,
+    max_states: usize,
+) -> Result<(Vec<(DotBracketVec, i32)>, bool), String> {
+    let mut walker = LoopNeighbors::try_from((sequence, start, model, NoShift))?;
+    let mut states = Vec::new();
+    let complete = walker.generate_neighbors_until(i32::MAX / 2, usize::MAX, |db, en| {
+        if states.len() == max_states {
+            return false;
+        }
+        states.push((db.clone(), en));
+        true
+    });
+    states.sort_by_key(|(_, en)| *en);
+    Ok((states, !complete))
+}
+
+/// The Boltzmann equilibrium occupancy of every macrostate in the registry,
+/// in the order of the registry. All secondary structures of the sequence 
+/// are enumerated (see `landscape`) and classified, hence this is only 
+/// feasible for short sequences. Fails if there are more than `max_states`
+/// structures.
+pub fn equilibrium_occupancy<E: EnergyModel>(
+    registry: &MacrostateRegistry<E>,
+    max_states: usize,
+) -> Result<Vec<(String, f64)>, String> {
+    let sequence = registry.sequence().clone();
+    let model = Arc::clone(registry.energy_model());
+    let kt = KB * (model.temperature() + K0);
+    let open = PairTable::try_from(".".repeat(sequence.len()).as_str())
+        .map_err(|e| e.to_string())?;
+
+    let (states, truncated) = landscape(&open, sequence, model, max_states)?;
+    if truncated {
+        return Err(format!("More than {} structures, cannot enumerate the ensemble.", max_states));
+    }
+    // States are sorted by energy, weights are relative to the MFE.
+    let mfe = states.first().map_or(0, |&(_, en)| en);
+    let mut weights = vec![0.0; registry.len()];
+    for (structure, en) in &states {
+        weights[registry.classify(structure)] += (-((en - mfe) as f64) / DCAL_PER_KCAL / kt).exp();
+    }
+    let z: f64 = weights.iter().sum();
+    Ok(registry.iter()
+        .map(|(idx, m)| (m.name().to_string(), weights[idx] / z))
+        .collect())
+}
+
+/// The direct path from `start` to `target`: first all pairs that are not
+/// in `target` are removed, then all pairs that are only in `target` are 
+/// added (each in 5' to 3' order), such that no intermediate structure 
+/// contains a pseudoknot. 
+///
+/// Returns all structures along the path (including `start` and `target`)
+/// and the barrier, i.e. the highest free energy along the path relative
+/// to the free energy of `start`.
+pub fn direct_path<E: EnergyModel>(
+    start: &PairTable,
+    target: &PairTable,
+    model: &E,
+    sequence: &NucleotideVec,
+) -> Result<(Vec<PairTable>, i32), String> {
+    if start.len() != target.len() || start.len() != sequence.len() {
+        return Err(format!("Length mismatch: start {}, target {}, sequence {}.", 
+            start.len(), target.len(), sequence.len()));
+    }
+    let energy = |pt: &PairTable| model.energy_of_structure(sequence, pt)
+        .map_err(|e| e.to_string());
+
+    let mut current = start.clone();
+    let e_start = energy(&current)?;
+    let mut e_max = e_start;
+    let mut path = vec![current.clone()];
+
+    let pairs = |from: &PairTable, other: &PairTable| -> Vec<Move> {
+        from.iter().enumerate()
+            .filter_map(|(i, &j)| j.map(|j| (i as NAIDX, j)))
+            .filter(|&(i, j)| i < j && other[i] != Some(j))
+            .map(|(i, j)| Move::Add { i, j })
+            .collect()
+    };
+    let removals = pairs(start, target).into_iter().map(|mv| mv.inverse());
+    let additions = pairs(target, start).into_iter();
+    for mv in removals.chain(additions) {
+        current.apply_move(&mv);
+        e_max = e_max.max(energy(&current)?);
+        path.push(current.clone());
     }
+    debug_assert_eq!(&current, target);
+    Ok((path, e_max - e_start))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff_energy::ViennaRNA;
+    use ff_energy::parameters::RNA_TURNER_2004;
+
+    /// Brute-force enumeration of all secondary structures in [i, j).
+    fn all_structures<E: EnergyModel>(seq: &NucleotideVec, model: &E, i: usize, j: usize) -> Vec<String> {
+        if i >= j {
+            return vec![String::new()];
+        }
+        let mut result: Vec<String> = all_structures(seq, model, i + 1, j)
+            .into_iter().map(|s| format!(".{s}")).collect();
+        for k in (i + model.min_hairpin_size() + 1)..j {
+            if !model.can_pair(seq[i], seq[k]) {
+                continue;
+            }
+            for inner in all_structures(seq, model, i + 1, k) {
+                for outer in all_structures(seq, model, k + 1, j) {
+                    result.push(format!("({inner}){outer}"));
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_shift_moves() {
+        let mut pt = PairTable::try_from(".((...))....((...))").unwrap();
+
+        // Inner end slides within the hairpin, outer end slides into the exterior loop.
+        assert_eq!(pt.try_shift(Pair::new(2, 6), 5), Ok(Pair::new(2, 5)));
+        assert_eq!(pt.try_shift(Pair::new(1, 7), 9), Ok(Pair::new(1, 9)));
+        assert_eq!(pt.try_shift(Pair::new(1, 7), 0), Ok(Pair::new(0, 7)));
 
+        // Crossing a helix, or leaving the adjacent loops is rejected.
+        assert!(pt.try_shift(Pair::new(1, 7), 4).is_err());
+        assert!(pt.try_shift(Pair::new(12, 18), 4).is_err());
+        assert!(pt.try_shift(Pair::new(1, 7), 2).is_err());
+        assert!(pt.try_shift(Pair::new(1, 6), 0).is_err());
+
+        assert_eq!(pt.apply_shift(Pair::new(1, 7), 9), Ok(Pair::new(1, 9)));
+        assert_eq!(pt, PairTable::try_from(".((...)..)..((...))").unwrap());
+
+        let mut db = DotBracketVec::try_from(".((...))....((...))").unwrap();
+        assert_eq!(db.apply_shift(Pair::new(12, 18), 10), Ok(Pair::new(10, 18)));
+        assert_eq!(db.to_string(), ".((...))..(..(...))");
+        assert!(db.apply_shift(Pair::new(10, 18), 4).is_err());
+        assert_eq!(db.to_string(), ".((...))..(..(...))");
+    }
+
+    #[test]
+    fn test_landscape_matches_brute_force() {
+        let model = Arc::new(ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0));
+        let seq = NucleotideVec::try_from("GGGAGAAACUCCC").unwrap();
+        let start = PairTable::try_from(".............").unwrap();
+
+        let (states, truncated) = landscape(&start, seq.clone(), Arc::clone(&model), usize::MAX).unwrap();
+        assert!(!truncated);
+        assert!(states.windows(2).all(|w| w[0].1 <= w[1].1));
+
+        let brute: Vec<(String, i32)> = all_structures(&seq, &*model, 0, seq.len())
+            .into_iter()
+            .map(|s| {
+                let pt = PairTable::try_from(s.as_str()).unwrap();
+                let en = model.energy_of_structure(&seq, &pt).unwrap();
+                (s, en)
+            }).collect();
+        assert_eq!(states.len(), brute.len());
+
+        let mfe = brute.iter().map(|(_, en)| *en).min().unwrap();
+        assert_eq!(states[0].1, mfe);
+        assert!(brute.contains(&(states[0].0.to_string(), mfe)));
+
+        let (capped, truncated) = landscape(&start, seq, model, 5).unwrap();
+        assert!(truncated);
+        assert_eq!(capped.len(), 5);
+    }
+
+    #[test]
+    fn test_equilibrium_occupancy() {
+        let model = Arc::new(ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0));
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq.clone(), Arc::clone(&model)));
+        let input = b">toggle\nGGGAAACCC\nopen .........\nhairpin (((...)))\n";
+        registry.insert_from_reader(std::io::Cursor::new(input), "toggle").unwrap();
+
+        let occupancy = equilibrium_occupancy(&registry, 1000).unwrap();
+        let names: Vec<&str> = occupancy.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["Unassigned", "open", "hairpin"]);
+        assert!((occupancy.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-12);
+
+        let energy = |db: &str| model.energy_of_structure(&seq, &PairTable::try_from(db).unwrap()).unwrap();
+        let delta_e = (energy("(((...)))") - energy(".........")) as f64 / DCAL_PER_KCAL;
+        let kt = KB * (37.0 + K0);
+        let ratio = occupancy[2].1 / occupancy[1].1;
+        assert!((ratio - (-delta_e / kt).exp()).abs() < 1e-9 * ratio);
+
+        assert!(equilibrium_occupancy(&registry, 3).is_err());
+    }
+
+    #[test]
+    fn test_direct_path() {
+        let model = ViennaRNA::default();
+        let seq = NucleotideVec::try_from("GGGAAACCCAAAGGGAAACCC").unwrap();
+        let start = PairTable::try_from("(((......)))........").unwrap();
+        let target = PairTable::try_from("............(((...)))").unwrap();
+        assert!(direct_path(&start, &target, &model, &seq).is_err());
+
+        let start = PairTable::try_from("(((...)))............").unwrap();
+        let (path, barrier) = direct_path(&start, &target, &model, &seq).unwrap();
+        assert_eq!(path.len(), 7);
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&target));
+        // All removals precede the additions, passing the open chain.
+        let open = PairTable::try_from(".....................").unwrap();
+        assert_eq!(path[3], open);
+
+        let energies: Vec<i32> = path.iter()
+            .map(|pt| model.energy_of_structure(&seq, pt).unwrap())
+            .collect();
+        assert_eq!(barrier, energies.iter().max().unwrap() - energies[0]);
+        assert!(barrier >= energies[3] - energies[0]);
+
+        let (path, barrier) = direct_path(&start, &start, &model, &seq).unwrap();
+        assert_eq!(path, vec![start]);
+        assert_eq!(barrier, 0);
+    }
+}
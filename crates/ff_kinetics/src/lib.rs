@@ -6,10 +6,14 @@ pub mod enum_neighbors;
 
 mod rate_model;
 mod stochastic_simulation;
+mod ensemble;
+mod simulation;
 mod macrostates;
 mod movesets;
 
 pub use rate_model::*;
 pub use stochastic_simulation::*;
+pub use ensemble::*;
+pub use simulation::*;
 pub use macrostates::*;
 pub use movesets::*;
@@ -5,12 +5,15 @@ use std::io;
 use std::sync::Arc;
 use std::path::PathBuf;
 use rustc_hash::FxHashMap;
+use ndarray::Array2;
 use rand::Rng;
+use log::warn;
 
 use ff_structure::DotBracketVec;
 use ff_structure::PairTable;
 use ff_energy::NucleotideVec;
 use ff_energy::EnergyModel;
+use ff_energy::DCAL_PER_KCAL;
 
 use crate::{K0, KB};
 
@@ -33,6 +36,8 @@ use crate::{K0, KB};
 /// - `ensemble`: Mapping from secondary structure representations `s` to `(E(s),
 ///   P(s|α))`.
 /// - `ensemble_energy`: The free energy of the macrostate `(P(α))`.
+/// - `basin`: For macrostates defined by a reference structure and a radius,
+///   see `Macrostate::from_basin`.
 ///
 /// # Notes
 /// - The `MacrostateRegisty` initializes a "**catch-all** macrostate", which is
@@ -64,6 +69,7 @@ pub struct Macrostate {
     name: String,
     ensemble: FxHashMap<DotBracketVec, (i32, f64)>,
     ensemble_energy: Option<f64>,
+    basin: Option<(DotBracketVec, usize)>,
 }
 
 impl Macrostate {
@@ -74,9 +80,25 @@ impl Macrostate {
             name: name.to_owned(),
             ensemble: FxHashMap::default(),
             ensemble_energy: None,
+            basin: None,
         }
     }
 
+    /// A macrostate of all structures within base-pair distance `radius` 
+    /// of the `reference` structure. The ensemble contains only the 
+    /// reference structure.
+    pub fn from_basin<E: EnergyModel>(
+        name: &str, 
+        sequence: &NucleotideVec,
+        reference: &DotBracketVec, 
+        radius: usize,
+        energy_model: &E, 
+    ) -> Self {
+        let mut macrostate = Self::from_list(name, sequence, std::slice::from_ref(reference), energy_model);
+        macrostate.basin = Some((reference.clone(), radius));
+        macrostate
+    }
+
     pub fn from_list<E: EnergyModel>(
         name: &str, 
         sequence: &NucleotideVec,
@@ -92,7 +114,7 @@ impl Macrostate {
                 .expect("Invalid dot-bracket for energy evaluation");
             let en = energy_model.energy_of_structure(sequence, &pt)
                 .expect("Broken energy evaluation!");
-            let q = (-en as f64 / 100.0 / rt).exp();
+            let q = (-en as f64 / DCAL_PER_KCAL / rt).exp();
             ensemble.insert(dbv.clone(), (en, q));
             q_sum += q;
         }
@@ -104,6 +126,7 @@ impl Macrostate {
             name: name.to_owned(),
             ensemble,
             ensemble_energy: Some(-rt * q_sum.ln()),
+            basin: None,
         }
     }
 
@@ -128,9 +151,26 @@ impl Macrostate {
         self.ensemble.is_empty()
     }
     
+    /// The reference structure and radius of a basin macrostate.
+    pub fn basin(&self) -> Option<(&DotBracketVec, usize)> {
+        self.basin.as_ref().map(|(reference, radius)| (reference, *radius))
+    }
+
+    /// The base-pair distance of a structure to the reference of a basin 
+    /// macrostate, if it is within the radius.
+    pub fn basin_distance(&self, structure: &DotBracketVec) -> Option<usize> {
+        let (reference, radius) = self.basin.as_ref()?;
+        let distance = structure.base_pair_distance(reference)
+            .expect("Invalid structure for base-pair distance");
+        (distance <= *radius).then_some(distance)
+    }
+
     /// Check if a secondary structure is contained in this macrostate.
     pub fn contains(&self, structure: &DotBracketVec) -> bool {
-        self.ensemble.contains_key(structure)
+        match self.basin {
+            Some(_) => self.basin_distance(structure).is_some(),
+            None => self.ensemble.contains_key(structure),
+        }
     }
 
     pub fn get_lowest_microstate(&self) -> Option<&DotBracketVec> {
@@ -161,7 +201,7 @@ impl Macrostate {
             }
             r -= p;
         }
-        eprintln!("WARNING: rounding error observed. This should be rare!");
+        warn!("Macrostate: rounding error observed. This should be rare!");
         self.ensemble.keys().next().cloned()
     }
 }
@@ -202,8 +242,17 @@ impl<E: EnergyModel> MacrostateRegistry<E> {
         Ok(())
     }
 
+    /// Read macrostates from a FASTA-like input: a header line (`>name`), the
+    /// sequence, and one structure per line. 
+    ///
+    /// Structure lines are either plain dot-brackets, which are grouped under
+    /// the header name, or labeled (`label dotbracket`), which are grouped
+    /// under their label. A single input can thus define several macrostates.
+    /// Any further data on a structure line is ignored. Macrostate names
+    /// must be alphanumeric (or '_') and unique within the registry.
     pub fn insert_from_reader<R: BufRead>(&mut self, reader: R, source: &str
     ) -> io::Result<()> {
+        let is_name = |token: &str| token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
 
         let mut lines = reader.lines();
 
@@ -218,7 +267,7 @@ impl<E: EnergyModel> MacrostateRegistry<E> {
                 .split_whitespace()
                 .next()
                 .ok_or_else(|| io_err("Header line is empty", source))?;
-            if token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            if is_name(token) {
                 token.to_string()
             } else {
                 return Err(io_err("Header name must be alphanumeric", source));
@@ -237,26 +286,35 @@ impl<E: EnergyModel> MacrostateRegistry<E> {
             return Err(io_err("Sequence does not match input sequence", source));
         }
 
-        let mut structures = Vec::new();
+        // Structures grouped by macrostate name, in order of appearance.
+        let mut groups: Vec<(String, Vec<DotBracketVec>)> = Vec::new();
         let mut warned_trailing = false;
         for (lineno, line_raw) in lines.enumerate() {
             let line = line_raw?.trim().to_string();
             if line.is_empty() || line.starts_with('#') {
                 continue;
             }
-            let structure_str = match line.split_once(char::is_whitespace) {
-                Some((s, _)) => {
-                    if !warned_trailing {
-                        eprintln!("Warning: trailing data after dot-bracket structures is ignored in {}.", source);
-                        warned_trailing = true;
-                    }
-                    s
-                },
-                None => &line,
+            let mut tokens = line.split_whitespace();
+            let first = tokens.next().expect("line is not empty");
+            let (label, structure_str) = if is_name(first) {
+                let s = tokens.next().ok_or_else(|| io_err(
+                    &format!("Missing structure for label '{}' at line {}", first, lineno + 3),
+                    source,
+                ))?;
+                (first, s)
+            } else {
+                (name.as_str(), first)
             };
+            if tokens.next().is_some() && !warned_trailing {
+                warn!("Trailing data after dot-bracket structures is ignored in {}.", source);
+                warned_trailing = true;
+            }
 
             match DotBracketVec::try_from(structure_str) {
-                Ok(dbv) => structures.push(dbv),
+                Ok(dbv) => match groups.iter_mut().find(|(n, _)| n == label) {
+                    Some((_, structures)) => structures.push(dbv),
+                    None => groups.push((label.to_string(), vec![dbv])),
+                },
                 Err(e) => {
                     return Err(io_err(
                         &format!("Invalid secondary structure at line {}: {:?}", lineno + 3, e),
@@ -266,34 +324,67 @@ impl<E: EnergyModel> MacrostateRegistry<E> {
             }
         }
 
-        if structures.is_empty() {
+        if groups.is_empty() {
             return Err(io_err("No structures found", source));
         }
 
-        self.macrostates.push(Macrostate::from_list(
-            &name,
+        for (label, _) in &groups {
+            if self.macrostates.iter().any(|m| m.name() == label) {
+                return Err(io_err(&format!("Duplicate macrostate name '{}'", label), source));
+            }
+        }
+
+        for (label, structures) in groups {
+            self.macrostates.push(Macrostate::from_list(
+                &label,
+                &self.sequence,
+                &structures,
+                &*self.energy_model,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Add a macrostate of all structures within base-pair distance 
+    /// `radius` of `reference`, see `classify`.
+    pub fn insert_basin(&mut self, name: &str, reference: &DotBracketVec, radius: usize
+    ) -> io::Result<()> {
+        if self.macrostates.iter().any(|m| m.name() == name) {
+            return Err(io_err(&format!("Duplicate macrostate name '{}'", name), "basin"));
+        }
+        if reference.len() != self.sequence.len() {
+            return Err(io_err("Reference structure does not match sequence length", name));
+        }
+        self.macrostates.push(Macrostate::from_basin(
+            name,
             &self.sequence,
-            &structures,
+            reference,
+            radius,
             &*self.energy_model,
         ));
         Ok(())
     }
 
     /// Try to classify a structure:
-    /// - Returns Some(index) if exactly one macrostate contains the structure
-    /// - Returns None if no macrostate matches
-    /// - Panics if more than one macrostate matches (unimplemented)
+    /// - Returns the index of the macrostate whose list contains the structure
+    /// - Otherwise, returns the index of the basin macrostate with the nearest
+    ///   reference within its radius (the first one in case of ties)
+    /// - Returns 0 (unassigned) if no macrostate matches
+    /// - Panics if more than one listed macrostate matches (unimplemented)
     pub fn classify(&self, structure: &DotBracketVec) -> usize {
         let mut matches = Vec::new();
 
         for (i, ms) in self.macrostates.iter().enumerate() {
-            if ms.contains(structure) {
+            if ms.basin.is_none() && ms.contains(structure) {
                 matches.push(i);
             }
         }
 
         match matches.len() {
-            0 => 0,
+            0 => self.macrostates.iter().enumerate()
+                .filter_map(|(i, ms)| ms.basin_distance(structure).map(|d| (d, i)))
+                .min()
+                .map_or(0, |(_, i)| i),
             1 => matches[0],
             _ => {
                 // For now: raise a panic, since overlapping macrostates are ambiguous
@@ -302,10 +393,19 @@ impl<E: EnergyModel> MacrostateRegistry<E> {
         }
     }
 
+    /// The index of the macrostate with the given name.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.macrostates.iter().position(|m| m.name() == name)
+    }
+
     pub fn sequence(&self) -> &NucleotideVec {
         &self.sequence
     }
 
+    pub fn energy_model(&self) -> &Arc<E> {
+        &self.energy_model
+    }
+
     pub fn macrostates(&self) -> &Vec<Macrostate> {
         &self.macrostates
     }
@@ -326,6 +426,63 @@ impl<E: EnergyModel> MacrostateRegistry<E> {
     }
 }
 
+/// Counts of transitions between macrostates together with the total 
+/// residence time in every macrostate, collected from trajectories.
+/// Indices are those of the `MacrostateRegistry`.
+#[derive(Debug, Clone)]
+pub struct TransitionMatrix {
+    counts: Array2<f64>,
+    residence: Vec<f64>,
+}
+
+impl TransitionMatrix {
+    pub fn new(num_macrostates: usize) -> Self {
+        Self {
+            counts: Array2::zeros((num_macrostates, num_macrostates)),
+            residence: vec![0.0; num_macrostates],
+        }
+    }
+
+    pub fn add_transition(&mut self, from: usize, to: usize) {
+        self.counts[[from, to]] += 1.0;
+    }
+
+    pub fn add_residence(&mut self, macro_idx: usize, time: f64) {
+        self.residence[macro_idx] += time;
+    }
+
+    /// Number of observed transitions from i to j at index [i, j].
+    pub fn counts(&self) -> &Array2<f64> {
+        &self.counts
+    }
+
+    /// Total time spent in each macrostate.
+    pub fn residence(&self) -> &[f64] {
+        &self.residence
+    }
+
+    /// Empirical rates count(i -> j) / time_in_i at index [i, j]. 
+    /// Rows of macrostates that were never visited are 0, and so is the diagonal.
+    pub fn rates(&self) -> Array2<f64> {
+        let mut rates = self.counts.clone();
+        for (mut row, &time) in rates.rows_mut().into_iter().zip(&self.residence) {
+            if time > 0.0 {
+                row /= time;
+            }
+        }
+        rates
+    }
+
+    pub fn merge(&mut self, other: &TransitionMatrix) {
+        assert_eq!(self.residence.len(), other.residence.len(),
+            "Cannot merge transition matrices of different size");
+        self.counts += &other.counts;
+        for (a, b) in self.residence.iter_mut().zip(&other.residence) {
+            *a += b;
+        }
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -416,5 +573,132 @@ mod tests {
         let all_names: Vec<_> = registry.iter().map(|(_, ms)| ms.name().to_string()).collect();
         assert!(all_names.contains(&"Unassigned".to_string()));
         assert!(all_names.contains(&"test".to_string()));
+        assert_eq!(registry.index_of("test"), Some(1));
+        assert_eq!(registry.index_of("missing"), None);
+    }
+
+    #[test]
+    fn test_macrostateregistry_labeled_structures() {
+        let energy_model = ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0);
+        let seq = NucleotideVec::try_from("UCAGUCUUCGCUGCGCUGUAUCGAUUCGGUUUCAGUUUUUAUUGC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq, Arc::new(energy_model)));
+
+        let input = b">labeled
+        UCAGUCUUCGCUGCGCUGUAUCGAUUCGGUUUCAGUUUUUAUUGC
+        both  .((((....)))).((((........))))...............
+        left  .((((....))))................................
+        both  .((((....)))).((((.(....).))))...............
+        ";
+        registry.insert_from_reader(Cursor::new(input), "labeled").unwrap();
+        assert_eq!(registry.len(), 3);
+        assert_eq!(registry.macrostates()[1].name(), "both");
+        assert_eq!(registry.macrostates()[1].len(), 2);
+        assert_eq!(registry.macrostates()[2].name(), "left");
+        assert_eq!(registry.macrostates()[2].len(), 1);
+
+        let s = DotBracketVec::try_from(".((((....)))).((((.(....).))))...............").unwrap();
+        assert_eq!(registry.classify(&s), 1);
+        let s = DotBracketVec::try_from(".((((....))))................................").unwrap();
+        assert_eq!(registry.classify(&s), 2);
+
+        // Legacy format: unlabeled structures use the header name.
+        let input = b">right
+        UCAGUCUUCGCUGCGCUGUAUCGAUUCGGUUUCAGUUUUUAUUGC
+        ..............((((........))))...............
+        ";
+        registry.insert_from_reader(Cursor::new(input), "legacy").unwrap();
+        assert_eq!(registry.len(), 4);
+        assert_eq!(registry.macrostates()[3].name(), "right");
+
+        // Names must be unique across inputs.
+        let input = b">other
+        UCAGUCUUCGCUGCGCUGUAUCGAUUCGGUUUCAGUUUUUAUUGC
+        left .(((......)))................................
+        ";
+        let err = registry.insert_from_reader(Cursor::new(input), "dup").unwrap_err();
+        assert!(err.to_string().contains("Duplicate macrostate name 'left'"));
+        assert_eq!(registry.len(), 4);
+    }
+
+    #[test]
+    fn test_basin_macrostates() {
+        let energy_model = ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0);
+        let seq = NucleotideVec::try_from("GGGGAAAACCCC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq, Arc::new(energy_model)));
+        let reference = DotBracketVec::try_from("((((....))))").unwrap();
+        registry.insert_basin("helix", &reference, 3).unwrap();
+        assert_eq!(registry.macrostates()[1].basin(), Some((&reference, 3)));
+        assert_eq!(registry.macrostates()[1].get_lowest_microstate(), Some(&reference));
+
+        let d2 = DotBracketVec::try_from("..((....))..").unwrap();
+        let d5 = DotBracketVec::try_from("(.........).").unwrap();
+        assert_eq!(registry.classify(&reference), 1);
+        assert_eq!(registry.classify(&d2), 1);
+        assert_eq!(registry.classify(&d5), 0);
+        assert!(registry.macrostates()[1].contains(&d2));
+        assert!(!registry.macrostates()[1].contains(&d5));
+
+        // The nearest reference wins, the first basin on ties.
+        let open = DotBracketVec::try_from("............").unwrap();
+        registry.insert_basin("open", &open, 3).unwrap();
+        registry.insert_basin("open2", &open, 3).unwrap();
+        assert_eq!(registry.classify(&DotBracketVec::try_from("...(....)...").unwrap()), 2);
+        assert_eq!(registry.classify(&DotBracketVec::try_from(".(((....))).").unwrap()), 1);
+        assert_eq!(registry.classify(&d2), 1);
+
+        // Listed structures take precedence over basins.
+        let input = b">listed\nGGGGAAAACCCC\n..((....))..\n";
+        registry.insert_from_reader(Cursor::new(input), "listed").unwrap();
+        assert_eq!(registry.classify(&d2), 4);
+
+        assert!(registry.insert_basin("helix", &open, 1).is_err());
+        assert!(registry.insert_basin("short", &DotBracketVec::try_from("....").unwrap(), 1).is_err());
+    }
+
+    /// Collects warnings so tests can check what the library reports.
+    struct CaptureLogger;
+    static CAPTURED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                CAPTURED.lock().unwrap().push(record.args().to_string());
+            }
+        }
+        fn flush(&self) {}
+    }
+
+    /// Installs the CaptureLogger once per test binary. (The logger is 
+    /// global, so it cannot be set by every test that needs it.)
+    fn capture_warnings() {
+        static LOGGER: CaptureLogger = CaptureLogger;
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&LOGGER).expect("no other logger in tests");
+            log::set_max_level(log::LevelFilter::Warn);
+        });
+    }
+
+    #[test]
+    fn test_trailing_data_warning_is_logged() {
+        capture_warnings();
+
+        let energy_model = ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0);
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq, Arc::new(energy_model)));
+
+        let input = b">hp
+        GGGAAACCC
+        (((...))) -1.20
+        ";
+        CAPTURED.lock().unwrap().clear();
+        registry.insert_from_reader(Cursor::new(input), "manual").unwrap();
+        assert_eq!(registry.len(), 2);
+
+        let captured = CAPTURED.lock().unwrap();
+        assert!(captured.iter().any(|m| m.contains("Trailing data") && m.contains("manual")));
     }
 }
@@ -1,6 +1,7 @@
+use serde::{Serialize, Deserialize};
 use ff_structure::NAIDX;
 
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Move {
     /// A pair formation.
     Add {
@@ -46,6 +46,10 @@ impl FourWayNeighbors {
         self.map.remove(index)
     }
 
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
     /// Calculate neighbors and return reference.
     pub fn compute_neighbors<E: EnergyModel>(
         &mut self,
@@ -16,6 +16,9 @@ use crate::shift_policy::ShiftPolicy;
 
 type Moves = Vec<(Move, i32)>;
 
+/// Base-pair moves as (i, j, delta_e).
+pub type PairMoves = Vec<(usize, usize, i32)>;
+
 pub struct LoopNeighbors<E: EnergyModel, P: ShiftPolicy> {
     loop_table: LoopTable<E>,
     add_neighbors: IntMap<usize, Moves>,
@@ -38,6 +41,29 @@ impl<E: EnergyModel, P: ShiftPolicy> LoopNeighbors<E, P> {
         &self.del_neighbors
     }
 
+    /// A new walker for the same sequence, energy model and shift policy, 
+    /// starting from the given structure.
+    pub fn with_structure<T: LoopDecomposition>(&self, pairings: &T) -> Result<Self, String> {
+        let ltab = LoopTable::try_from((
+            self.loop_table.sequence().clone(), 
+            pairings, 
+            Arc::clone(self.loop_table.model()),
+        ))?;
+        Ok(LoopNeighbors::from((ltab, self._policy)))
+    }
+
+    /// Start over from the given structure, reusing the allocated memory
+    /// of the loop table and the neighbor maps.
+    pub fn reset<T: LoopDecomposition>(&mut self, pairings: &T) {
+        self.loop_table.reset(pairings);
+        self.add_neighbors.clear();
+        self.del_neighbors.clear();
+        self.three_way_shift_neighbors.clear();
+        self.four_way_shift_neighbors.clear();
+        self.init_del_neighbors();
+        self.init_loop_neighbors();
+    }
+
     pub fn three_way_shift_neighbors(&self) -> &ThreeWayNeighbors {
         &self.three_way_shift_neighbors
     }
@@ -46,6 +72,25 @@ impl<E: EnergyModel, P: ShiftPolicy> LoopNeighbors<E, P> {
         &self.four_way_shift_neighbors
     }
 
+    /// All base-pair additions and deletions of the current structure as 
+    /// (i, j, delta_e), each list sorted by (i, j). Shift moves are not 
+    /// included, see `Walker::propose_moves` for all moves.
+    pub fn enumerate_moves(&self) -> (PairMoves, PairMoves) {
+        let mut add: Vec<_> = self.add_neighbors.values()
+            .flatten()
+            .filter_map(|&(mv, delta_e)| match mv {
+                Move::Add { i, j } => Some((i as usize, j as usize, delta_e)),
+                _ => None,
+            })
+            .collect();
+        let mut del: Vec<_> = self.del_neighbors.iter()
+            .map(|(&i, &delta_e)| (i as usize, self.loop_table.pair_lookup(&i) as usize, delta_e))
+            .collect();
+        add.sort_unstable();
+        del.sort_unstable();
+        (add, del)
+    }
+
     /// Activation energy -> for add moves it is delta-E
     /// We should be able to change this for alternative 
     /// rate models, but do not break detailed balance.
@@ -691,6 +736,7 @@ for LoopNeighbors<E, P>
 mod tests {
     use super::*;
     use ff_structure::PairTable;
+    use ff_structure::DotBracketVec;
     use ff_energy::ViennaRNA;
     use ff_energy::NucleotideVec;
     use ff_energy::parameters::RNA_TURNER_2004;
@@ -735,6 +781,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_enumerate_moves() {
+        let model = ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0);
+        let seq = NucleotideVec::try_from("GGGAGAAACUCCCA").unwrap();
+        let pt = PairTable::try_from(".((.......))..").unwrap();
+        let energy = model.energy_of_structure(&seq, &pt).unwrap();
+
+        // Manual enumeration from the neighbors of the pair table.
+        let (mut add, mut del) = (Vec::new(), Vec::new());
+        for nb in pt.neighbors() {
+            let i = (0..pt.len()).find(|&k| nb[k] != pt[k]).unwrap();
+            let delta_e = || model.energy_of_structure(&seq, &nb).unwrap() - energy;
+            match (pt[i], nb[i]) {
+                (Some(j), None) => del.push((i, j as usize, delta_e())),
+                (None, Some(j)) if model.can_pair(seq[i], seq[j as usize])
+                    && j as usize - i > model.min_hairpin_size() => add.push((i, j as usize, delta_e())),
+                _ => (),
+            }
+        }
+        add.sort_unstable();
+        del.sort_unstable();
+
+        setup_loop_table!(ltab, "GGGAGAAACUCCCA", ".((.......))..");
+        let adm = LoopNeighbors::from((ltab, NoShift));
+        let (mv_add, mv_del) = adm.enumerate_moves();
+        assert_eq!(mv_del.len(), 2);
+        assert!(!mv_add.is_empty());
+        assert_eq!(mv_add, add);
+        assert_eq!(mv_del, del);
+    }
+
+    #[test]
+    fn test_annotated_structure() {
+        setup_loop_table!(ltab, "GGGAAACCCAGGGAAAACCCA", 
+                                "(((...))).(((....))).");
+        let (db, ann) = ltab.annotated_structure();
+        assert_eq!(db, DotBracketVec::try_from("(((...))).(((....))).").unwrap());
+        assert_eq!(ann.iter().sum::<i32>(), ltab.energy());
+        assert!(ann.iter().enumerate().all(|(k, &a)| a == 0 || [0, 1, 2, 10, 11, 12].contains(&k)));
+
+        // Loops that were replaced by moves must not be counted.
+        let mut adm = LoopNeighbors::from((ltab, NoShift));
+        let _ = adm.apply_move(&Move::Del { i: 1, j: 7 });
+        let _ = adm.apply_move(&Move::Del { i: 11, j: 18 });
+        let _ = adm.apply_move(&Move::Add { i: 11, j: 18 });
+        let (db, ann) = adm.loop_table().annotated_structure();
+        assert_eq!(db, adm.current_structure());
+        assert_eq!(ann.iter().sum::<i32>(), adm.current_energy());
+    }
+
     #[test]
     fn test_development_bug01() {
         setup_loop_table!(ltab, "GCAUAGCCCA", 
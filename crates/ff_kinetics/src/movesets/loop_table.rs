@@ -3,6 +3,8 @@ use std::sync::Arc;
 use nohash_hasher::IntMap;
 
 use ff_structure::NAIDX;
+use ff_structure::Pair;
+use ff_structure::PairTable;
 use ff_structure::DotBracket;
 use ff_structure::DotBracketVec;
 use ff_energy::EnergyModel;
@@ -46,6 +48,14 @@ impl<E: EnergyModel> Clone for LoopTable<E> {
 }
 
 impl<E: EnergyModel> LoopTable<E> {
+    pub fn sequence(&self) -> &NucleotideVec {
+        &self.sequence
+    }
+
+    pub fn model(&self) -> &Arc<E> {
+        &self.model
+    }
+
     pub fn sequence_length(&self) -> usize {
         self.sequence.len()
     }
@@ -87,6 +97,25 @@ impl<E: EnergyModel> LoopTable<E> {
         self.energy
     }
 
+    /// The current structure with a per-position energy annotation: the
+    /// free energy of every loop is assigned to the 5' position of its 
+    /// closing pair, the exterior loop energy is assigned to position 0.
+    /// All other positions are annotated with 0, such that the annotations
+    /// sum up to the total energy of the structure.
+    pub fn annotated_structure(&self) -> (DotBracketVec, Vec<i32>) {
+        let mut annotation = vec![0; self.lookup_len()];
+        for (idx, (nn_loop, en)) in self.loops.iter().enumerate() {
+            if self.stale.contains(&idx) {
+                continue;
+            }
+            let k = nn_loop.closing().map_or(0, |(i, _)| i as usize);
+            if let Some(a) = annotation.get_mut(k) {
+                *a += en;
+            }
+        }
+        (DotBracketVec::from(self), annotation)
+    }
+
     pub fn extend_lookup(&mut self, idx: usize) {
         self.loop_lookup.push(idx);
     }
@@ -121,6 +150,38 @@ impl<E: EnergyModel> LoopTable<E> {
         self.stale.push(idx);
     }
 
+    /// Replace the loop decomposition by the one of `pairings`, 
+    /// reusing the allocated memory.
+    pub fn reset<T: LoopDecomposition>(&mut self, pairings: &T) {
+        self.loops.clear();
+        self.stale.clear();
+        self.loop_lookup.clear();
+        self.pair_lookup.clear();
+        self.energy = 0;
+
+        pairings.for_each_loop(|l| {
+            let (_, b) = l.span();
+            let b = b as usize + 1;
+            if b > self.loop_lookup.len() {
+                self.loop_lookup.resize(b, usize::MAX);
+            }
+
+            let loop_energy = self.model.energy_of_loop(&self.sequence, l)
+                .expect("Broken energy evaluation!");
+            self.energy += loop_energy;
+
+            if let Some((i, j)) = l.closing() {
+                self.pair_lookup.insert(i as NAIDX, j as NAIDX); 
+            }
+
+            let loop_index = self.loops.len();
+            for k in l.inclusive_unpaired_indices() {
+                self.loop_lookup[k] = loop_index;
+            }
+            self.loops.push((l.to_owned(), loop_energy));
+        });
+    }
+
     pub fn insert_loopentry(
         &mut self, 
         index: Option<usize>, 
@@ -176,6 +237,14 @@ impl<E: EnergyModel> From<&LoopTable<E>> for DotBracketVec {
     }
 }
 
+impl<E: EnergyModel> From<&LoopTable<E>> for PairTable {
+    fn from(ltab: &LoopTable<E>) -> Self {
+        let pairs = ltab.pairs().map(|(&i, &j)| Pair::new(i, j));
+        PairTable::try_from_pairs(ltab.lookup_len(), pairs)
+            .expect("LoopTable must hold a valid structure.")
+    }
+}
+
 impl<T: LoopDecomposition, E: EnergyModel> TryFrom<(NucleotideVec, &T, Arc<E>)> 
 for LoopTable<E> {
     type Error = String;
@@ -183,44 +252,40 @@ for LoopTable<E> {
     fn try_from((sequence, pairings, model): (NucleotideVec, &T, Arc<E>)
     ) -> Result<Self, Self::Error> {
 
-        let mut loops = Vec::new();
-        let mut loop_lookup = Vec::with_capacity(sequence.len());
-        let mut pair_lookup: IntMap<NAIDX, NAIDX>  = IntMap::default();
-        let mut energy = 0;
-
-        let mut current_len = 0;
-        pairings.for_each_loop(|l| {
-            let (_, b) = l.span();
-            let b = b as usize + 1;
-            if b > current_len {
-                loop_lookup.resize(b, usize::MAX);
-                current_len = b;
-            }
-
-            let loop_energy = model.energy_of_loop(&sequence, l)
-                .expect("Broken energy evaluation!");
-            energy += loop_energy;
-
-            if let Some((i, j)) = l.closing() {
-                pair_lookup.insert(i as NAIDX, j as NAIDX); 
-            }
-
-            let loop_index = loops.len();
-            for k in l.inclusive_unpaired_indices() {
-                loop_lookup[k] = loop_index;
-            }
-            loops.push((l.to_owned(), loop_energy));
-        });
-
-        Ok(LoopTable {
+        let mut ltab = LoopTable {
+            loop_lookup: Vec::with_capacity(sequence.len()),
             sequence,
             model,
-            loops,
+            loops: Vec::new(),
             stale: Vec::new(),
-            loop_lookup,
-            pair_lookup,
-            energy,
-        })
+            pair_lookup: IntMap::default(),
+            energy: 0,
+        };
+        ltab.reset(pairings);
+        Ok(ltab)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff_energy::ViennaRNA;
+    use crate::Walker;
+    use crate::LoopNeighbors;
+    use crate::Move;
+    use crate::shift_policy::NoShift;
+
+    #[test]
+    fn test_pair_table_from_loop_table() {
+        let sequence = NucleotideVec::try_from("GGGAAACCCAGGGAAAACCCA").unwrap();
+        let pairings = PairTable::try_from("(((...))).(((....))).").unwrap();
+        let model = Arc::new(ViennaRNA::default());
+        let ltab = LoopTable::try_from((sequence.clone(), &pairings, Arc::clone(&model))).unwrap();
+        assert_eq!(PairTable::from(&ltab), pairings);
+
+        let mut walker = LoopNeighbors::try_from((sequence, &pairings, model, NoShift)).unwrap();
+        walker.apply_move(&Move::Del { i: 11, j: 18 });
+        assert_eq!(PairTable::from(walker.loop_table()),
+            PairTable::try_from("(((...))).(.(....).).").unwrap());
+    }
+}
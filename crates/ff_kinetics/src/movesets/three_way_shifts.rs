@@ -49,6 +49,10 @@ impl ThreeWayNeighbors {
         self.map.remove(index)
     }
 
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
     // Calculate neighbors and return reference.
     pub fn compute_neighbors<E: EnergyModel>(
         &mut self,
@@ -1,5 +1,6 @@
 
 use ff_structure::DotBracketVec;
+use ff_structure::PairTable;
 use ff_energy::EnergyModel;
 
 use crate::shift_policy::ShiftPolicy;
@@ -28,6 +29,9 @@ pub trait Walker {
     fn apply_move(&mut self, mv: &Move) -> (Moves, Moves);
 
     fn apply_extension(&mut self) -> (Moves, Moves);
+
+    /// Start over from the given structure, reusing allocated memory.
+    fn reset(&mut self, pairings: &PairTable) -> Result<(), String>;
 }
 
 impl<E: EnergyModel, P: ShiftPolicy> Walker for LoopNeighbors<E, P> {
@@ -103,5 +107,14 @@ impl<E: EnergyModel, P: ShiftPolicy> Walker for LoopNeighbors<E, P> {
     fn apply_extension(&mut self) -> (Moves, Moves) {
         self.apply_ext_move()
     }
+
+    fn reset(&mut self, pairings: &PairTable) -> Result<(), String> {
+        if pairings.len() > self.sequence_length() {
+            return Err(format!("Structure length {} exceeds sequence length {}.", 
+                    pairings.len(), self.sequence_length()));
+        }
+        LoopNeighbors::reset(self, pairings);
+        Ok(())
+    }
 }
 
@@ -1,11 +1,37 @@
+use std::fmt;
+use std::error::Error;
+use ff_energy::DCAL_PER_KCAL;
+
 use crate::Move;
 
 pub const K0: f64 = 273.15;
 pub const KB: f64 = 0.001987204285; // kcal/(mol*K)
 
 pub trait RateModel: Send + Sync {
-    /// Given dE (in kcal/mol), return the rate constant.
+    /// Given dE (in dcal/mol), return the rate constant.
     fn rate(&self, m: &Move, delta_e: i32) -> f64;
+
+    /// The natural logarithm of the rate constant.
+    fn log_rate(&self, m: &Move, delta_e: i32) -> f64 {
+        self.rate(m, delta_e).ln()
+    }
+}
+
+/// How the Arrhenius model turns the free energy change of a move into
+/// an activation energy.
+///
+/// Both schemes satisfy detailed balance, as the forward and reverse rate
+/// of a move differ by exp(-dE/kT). With `Full`, no rate exceeds the 
+/// maximum rate (k0, k3ws or k4ws), with `Half` only uphill moves are 
+/// slower and downhill moves are faster than the maximum rate.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MoveScheme {
+    /// Metropolis: downhill moves at the maximum rate, uphill moves 
+    /// at k * exp(-dE/kT).
+    #[default]
+    Full,
+    /// The barrier is split between both directions: k * exp(-dE/(2kT)).
+    Half,
 }
 
 /// The Arrhenius rate model.
@@ -27,6 +53,8 @@ pub struct Arrhenius {
     k3ws: f64,
     /// The maximum rate for four-way shift moves (k_{4ws} = A_{4ws} * exp(-G_{4ws}/kT)) 
     k4ws: f64,
+    /// How dE enters the activation energy.
+    scheme: MoveScheme,
 }
 
 impl Arrhenius {
@@ -40,9 +68,20 @@ impl Arrhenius {
             k0,
             k3ws: k3ws.unwrap_or(0.0),
             k4ws: k4ws.unwrap_or(0.0),
+            scheme: MoveScheme::Full,
         }
     }
 
+    /// Select how dE enters the rates (default: `MoveScheme::Full`).
+    pub fn with_scheme(mut self, scheme: MoveScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn scheme(&self) -> MoveScheme {
+        self.scheme
+    }
+
     pub fn k0(&self) -> Option<f64> {
         if self.k0 > 0.0 {
             Some(self.k0)
@@ -71,29 +110,281 @@ impl Arrhenius {
 
 impl RateModel for Arrhenius {
     fn rate(&self, mv: &Move, delta_e: i32) -> f64 {
+        let k = match &mv {
+            Move::Add { .. } | Move::Del { .. } => self.k0,
+            Move::ShiftIK { .. } | Move::ShiftJK { .. } => self.k3ws,
+            Move::ShiftIKLJ { .. } | Move::ShiftILJK { .. } => self.k4ws,
+        };
+        match self.scheme {
+            MoveScheme::Full if delta_e <= 0 => k,
+            MoveScheme::Full => k * ((-delta_e as f64 / DCAL_PER_KCAL) / self.kt).exp(),
+            MoveScheme::Half => k * ((-delta_e as f64 / DCAL_PER_KCAL) / (2. * self.kt)).exp(),
+        }
+   }
+}    
+
+
+/// The Kawasaki rate model.
+///
+/// Forward and reverse rates split the free energy difference symmetrically:
+/// k = k0 * exp(-dE / (2kT)), hence detailed balance holds by construction.
+/// Shift moves are not supported by this model (their rate is 0).
+#[derive(Debug, Clone, Copy)]
+pub struct Kawasaki {
+    /// kT = k_Boltzmann * temperature [kcal/mol].
+    kt: f64,
+    /// The rate for base-pair formation/breaking at dE = 0.
+    k0: f64,
+}
+
+impl Kawasaki {
+    pub fn new(celsius: f64, k0: f64) -> Self {
+        if k0 < 0. {
+            panic!("k0 must not be negative!");
+        }
+        Self { 
+            kt: KB * (celsius + K0),
+            k0,
+        }
+    }
+}
+
+impl RateModel for Kawasaki {
+    fn rate(&self, mv: &Move, delta_e: i32) -> f64 {
+        self.log_rate(mv, delta_e).exp()
+    }
+
+    fn log_rate(&self, mv: &Move, delta_e: i32) -> f64 {
         match &mv {
             Move::Add { .. } | Move::Del { .. } => {
-                if delta_e <= 0 {
-                    self.k0
-                } else {
-                    self.k0 * ((-delta_e as f64 / 100.) / self.kt).exp()
-                }
-            },
-            Move::ShiftIK { .. } | Move::ShiftJK { .. } => {
-                if delta_e <= 0 {
-                    self.k3ws
-                } else {
-                    self.k3ws * ((-delta_e as f64 / 100.) / self.kt).exp()
-                }
-            }, 
-            Move::ShiftIKLJ { .. } | Move::ShiftILJK { .. } => {
-                if delta_e <= 0 {
-                    self.k4ws
-                } else {
-                    self.k4ws * ((-delta_e as f64 / 100.) / self.kt).exp()
-                }
+                self.k0.ln() - (delta_e as f64 / DCAL_PER_KCAL) / (2. * self.kt)
             },
+            _ => f64::NEG_INFINITY,
         }
-   }
-}    
+    }
+}
+
+/// A rate model backed by a closure that maps dE (dcal/mol) to a rate,
+/// for all types of moves.
+///
+/// # Example
+/// ```rust
+/// use ff_kinetics::{from_fn, RateModel, Move};
+///
+/// let model = from_fn(|delta_e| if delta_e <= 0 { 1.0 } else { 0.5 });
+/// assert_eq!(model.rate(&Move::Add { i: 0, j: 5 }, 100), 0.5);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ClosureModel<F> {
+    func: F,
+}
+
+impl<F: Fn(i32) -> f64 + Send + Sync> ClosureModel<F> {
+    pub fn new(func: F) -> Self {
+        Self { func }
+    }
+}
 
+impl<F: Fn(i32) -> f64 + Send + Sync> RateModel for ClosureModel<F> {
+    fn rate(&self, _mv: &Move, delta_e: i32) -> f64 {
+        (self.func)(delta_e)
+    }
+}
+
+/// Build a `RateModel` from a closure, see `ClosureModel`.
+pub fn from_fn<F: Fn(i32) -> f64 + Send + Sync>(func: F) -> ClosureModel<F> {
+    ClosureModel::new(func)
+}
+
+/// Bounds the rates of another rate model to [min_rate, max_rate].
+///
+/// The clamp is applied to the log rates, rates within the bounds remain 
+/// unchanged. Moves with rate 0 remain switched off. Note that clamping 
+/// breaks detailed balance for all moves where either the forward or the 
+/// reverse rate has been clamped.
+#[derive(Debug, Clone, Copy)]
+pub struct Clamped<K> {
+    model: K,
+    log_min: f64,
+    log_max: f64,
+}
+
+impl<K: RateModel> Clamped<K> {
+    pub fn new(model: K, min_rate: Option<f64>, max_rate: Option<f64>) -> Self {
+        let log_min = min_rate.map_or(f64::NEG_INFINITY, f64::ln);
+        let log_max = max_rate.map_or(f64::INFINITY, f64::ln);
+        if log_min.is_nan() || log_max.is_nan() || log_min > log_max {
+            panic!("Invalid rate bounds: need 0 <= min_rate <= max_rate!");
+        }
+        Self { model, log_min, log_max }
+    }
+
+    pub fn inner(&self) -> &K {
+        &self.model
+    }
+}
+
+impl<K: RateModel> RateModel for Clamped<K> {
+    fn rate(&self, mv: &Move, delta_e: i32) -> f64 {
+        self.log_rate(mv, delta_e).exp()
+    }
+
+    fn log_rate(&self, mv: &Move, delta_e: i32) -> f64 {
+        match self.model.log_rate(mv, delta_e) {
+            f64::NEG_INFINITY => f64::NEG_INFINITY,
+            lr => lr.clamp(self.log_min, self.log_max),
+        }
+    }
+}
+
+/// The worst violation of detailed balance found by `check_detailed_balance`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DbViolation {
+    /// The free energy change (dcal/mol) of the forward move.
+    pub delta_e: i32,
+    /// log_rate(dE) - log_rate(-dE) + dE/kT
+    pub residual: f64,
+}
+
+impl fmt::Display for DbViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Detailed balance violated at dE = {} (residual {:.3e})", 
+            self.delta_e, self.residual)
+    }
+}
+
+impl Error for DbViolation {}
+
+/// Verify that log_rate(dE) - log_rate(-dE) = -dE/kT for every given dE 
+/// (in dcal/mol), using the formation and the breaking of the same base-pair 
+/// as forward and reverse move. kT must be given in kcal/mol.
+pub fn check_detailed_balance<K: RateModel>(
+    model: &K, 
+    kt: f64, 
+    deltas: &[i32]
+) -> Result<(), DbViolation> {
+    let add = Move::Add { i: 0, j: 1 };
+    let del = Move::Del { i: 0, j: 1 };
+    let magnitude = |r: f64| if r.is_nan() { f64::INFINITY } else { r.abs() };
+    let mut worst: Option<DbViolation> = None;
+    for &delta_e in deltas {
+        let expected = -(delta_e as f64 / DCAL_PER_KCAL) / kt;
+        let residual = model.log_rate(&add, delta_e) - model.log_rate(&del, -delta_e) - expected;
+        let tolerance = 1e-9 * expected.abs().max(1.0);
+        if magnitude(residual) > tolerance
+            && worst.is_none_or(|w| magnitude(residual) > magnitude(w.residual)) {
+            worst = Some(DbViolation { delta_e, residual });
+        }
+    }
+    match worst {
+        None => Ok(()),
+        Some(v) => Err(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kawasaki_detailed_balance() {
+        let model = Kawasaki::new(37.0, 1e5);
+        let kt = KB * (37.0 + K0);
+        let add = Move::Add { i: 0, j: 10 };
+        let del = Move::Del { i: 0, j: 10 };
+
+        for delta_e in [-1250, -300, -1, 0, 1, 42, 300, 1250] {
+            let forward = model.log_rate(&add, delta_e);
+            let reverse = model.log_rate(&del, -delta_e);
+            let expected = -(delta_e as f64 / DCAL_PER_KCAL) / kt;
+            assert!((forward - reverse - expected).abs() < 1e-12);
+            assert!((model.rate(&add, delta_e) - forward.exp()).abs() <= 1e-9 * forward.exp());
+        }
+        assert_eq!(model.log_rate(&add, 0), 1e5_f64.ln());
+        assert_eq!(model.rate(&Move::ShiftIK { i: 0, j: 10, k: 11 }, -100), 0.0);
+    }
+
+    #[test]
+    fn test_check_detailed_balance() {
+        let kt = KB * (37.0 + K0);
+        let deltas = [-1250, -300, -100, 0, 100, 300, 1250];
+        assert!(check_detailed_balance(&Arrhenius::new(37.0, 1e5, None, None), kt, &deltas).is_ok());
+        assert!(check_detailed_balance(&Kawasaki::new(37.0, 1e5), kt, &deltas).is_ok());
+
+        // Correct, except for a sign error at |dE| = 300.
+        let broken = from_fn(move |d: i32| {
+            let d = if d.abs() == 300 { -d } else { d };
+            (-(d.max(0) as f64 / DCAL_PER_KCAL) / kt).exp()
+        });
+        let err = check_detailed_balance(&broken, kt, &deltas).unwrap_err();
+        assert_eq!(err.delta_e, -300);
+        assert!((err.residual.abs() - 6.0 / kt).abs() < 1e-9);
+        assert!(check_detailed_balance(&broken, kt, &[-100, 0, 100]).is_ok());
+    }
+
+    #[test]
+    fn test_move_scheme() {
+        let kt = KB * (37.0 + K0);
+        let full = Arrhenius::new(37.0, 1e5, Some(1e3), Some(1e2));
+        let half = full.with_scheme(MoveScheme::Half);
+        assert_eq!(full.scheme(), MoveScheme::Full);
+        assert_eq!(full.with_scheme(MoveScheme::Full).scheme(), MoveScheme::Full);
+        let moves = [
+            (Move::Add { i: 0, j: 10 }, 1e5),
+            (Move::Del { i: 0, j: 10 }, 1e5),
+            (Move::ShiftIK { i: 0, j: 10, k: 11 }, 1e3),
+            (Move::ShiftIKLJ { i: 0, j: 10, k: 3, l: 7 }, 1e2),
+        ];
+        for (mv, k) in moves {
+            for delta_e in [-1250, -300, -1, 0, 1, 42, 300, 1250] {
+                let de = delta_e as f64 / DCAL_PER_KCAL;
+                // The Metropolis rates of the default model.
+                let metropolis = if delta_e <= 0 { k } else { k * (-de / kt).exp() };
+                assert_eq!(full.rate(&mv, delta_e), metropolis);
+                assert_eq!(full.log_rate(&mv, delta_e), metropolis.ln());
+                let split = k * (-de / (2. * kt)).exp();
+                assert!((half.rate(&mv, delta_e) - split).abs() <= 1e-12 * split);
+            }
+        }
+        assert!(half.rate(&moves[0].0, -300) > 1e5);
+        assert!(check_detailed_balance(&half, kt, &[-1250, -300, 0, 300, 1250]).is_ok());
+    }
+
+    #[test]
+    fn test_clamped() {
+        let model = Clamped::new(Arrhenius::new(37.0, 1e5, None, None), Some(1e-2), Some(1e3));
+        let add = Move::Add { i: 0, j: 10 };
+        assert!((model.rate(&add, -500) - 1e3).abs() < 1e-9);
+        assert!((model.rate(&add, 5000) - 1e-2).abs() < 1e-12);
+        let inner = model.inner().log_rate(&add, 500);
+        assert!(model.log_rate(&add, 500) == inner && inner < 1e3_f64.ln());
+        // Ordering is preserved (non-strictly).
+        let rates: Vec<f64> = (-5..=50).map(|d| model.rate(&add, d * 100)).collect();
+        assert!(rates.windows(2).all(|w| w[0] >= w[1]));
+        // Disabled moves stay disabled.
+        assert_eq!(model.rate(&Move::ShiftIK { i: 0, j: 10, k: 11 }, -100), 0.0);
+
+        // Detailed balance holds only where neither direction is clamped.
+        let kt = KB * (37.0 + K0);
+        let model = Clamped::new(Arrhenius::new(37.0, 1.0, None, None), Some(1e-6), Some(10.0));
+        assert!(check_detailed_balance(&model, kt, &[-300, -100, 0, 100, 300]).is_ok());
+        assert!(check_detailed_balance(&model, kt, &[-1250, 1250]).is_err());
+    }
+
+    #[test]
+    fn test_energy_units() {
+        // -1 kcal/mol downhill, +1 kcal/mol uphill.
+        let kt = KB * (37.0 + K0);
+        let model = Arrhenius::new(37.0, 1.0, None, None);
+        let add = Move::Add { i: 0, j: 10 };
+        let del = Move::Del { i: 0, j: 10 };
+        assert_eq!(model.rate(&add, -100), 1.0);
+        let ratio = model.rate(&del, 100) / model.rate(&add, -100);
+        assert!((ratio - (-1.0 / kt).exp()).abs() < 1e-12);
+        assert!((ratio - 0.19735).abs() < 1e-4);
+
+        let model = Kawasaki::new(37.0, 1.0);
+        let ratio = model.rate(&add, -100) / model.rate(&del, 100);
+        assert!((ratio - (1.0 / kt).exp()).abs() < 1e-9);
+    }
+}
@@ -1,4 +1,5 @@
 use rustc_hash::FxHashMap;
+use log::warn;
 
 use crate::Move;
 
@@ -59,6 +60,15 @@ impl RateTree {
         self.entries.len() == 1
     }
 
+    pub fn contains(&self, mv: &Move) -> bool {
+        self.pos_map.contains_key(mv)
+    }
+
+    /// All moves with their rates (in no particular order).
+    pub fn iter(&self) -> impl Iterator<Item = (Move, f64)> + '_ {
+        self.entries[1..].iter().map(|n| (n.mv, n.rate))
+    }
+
     pub fn total_rate(&self) -> f64 {
         if self.is_empty() {
             0.0
@@ -67,6 +77,12 @@ impl RateTree {
         }
     }
     
+    /// Remove all moves, but keep the allocated memory.
+    pub fn clear(&mut self) {
+        self.entries.truncate(1);
+        self.pos_map.clear();
+    }
+
     pub fn init_insert(&mut self, mv: Move, rate: f64) {
         debug_assert!(rate > 0.0);
         let idx = self.entries.len();
@@ -217,7 +233,7 @@ impl RateTree {
                 break;
             }
         }
-        eprintln!("RateTree: roundoff error! This should be extremely rare!");
+        warn!("RateTree: roundoff error! This should be extremely rare!");
         self.entries
             .last()
             .map(|n| n.mv)
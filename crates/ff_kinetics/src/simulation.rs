@@ -0,0 +1,309 @@
+use std::io;
+use std::sync::Arc;
+use std::path::PathBuf;
+use ff_structure::PairTable;
+use ff_structure::DotBracketVec;
+use ff_energy::EnergyModel;
+use ff_energy::NucleotideVec;
+
+use crate::Event;
+use crate::Walker;
+use crate::EventSink;
+use crate::EnsembleHooks;
+use crate::Arrhenius;
+use crate::LoopNeighbors;
+use crate::MacrostateRegistry;
+use crate::run_ensemble_with;
+use crate::shift_policy::*;
+use crate::timeline::Timeline;
+use crate::timeline::Observable;
+
+/// Everything needed to simulate an ensemble of trajectories and collect
+/// them in a timeline, as done by `ff-timecourse`.
+///
+/// ```ignore
+/// let timeline = Simulation::new()
+///     .sequence(sequence)
+///     .energy_model(Arc::new(ViennaRNA::default()))
+///     .times(&[0.0, 1e-4, 1e-2])
+///     .num_sims(100)
+///     .run()?;
+/// ```
+pub struct Simulation<E: EnergyModel> {
+    sequence: Option<NucleotideVec>,
+    structure: Option<DotBracketVec>,
+    energy_model: Option<Arc<E>>,
+    rate_model: Option<Arrhenius>,
+    times: Vec<f64>,
+    num_sims: usize,
+    macrostates: Vec<PathBuf>,
+    seed: Option<u64>,
+    observables: Vec<Observable>,
+    absorbing: Vec<String>,
+    events: Option<EventSink>,
+    progress: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl<E: EnergyModel> Default for Simulation<E> {
+    fn default() -> Self {
+        Self {
+            sequence: None,
+            structure: None,
+            energy_model: None,
+            rate_model: None,
+            times: Vec::new(),
+            num_sims: 1,
+            macrostates: Vec::new(),
+            seed: None,
+            observables: Vec::new(),
+            absorbing: Vec::new(),
+            events: None,
+            progress: None,
+        }
+    }
+}
+
+impl<E: EnergyModel> Simulation<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sequence(mut self, sequence: NucleotideVec) -> Self {
+        self.sequence = Some(sequence);
+        self
+    }
+
+    /// The initial structure (default: the open chain).
+    pub fn structure(mut self, structure: DotBracketVec) -> Self {
+        self.structure = Some(structure);
+        self
+    }
+
+    pub fn energy_model(mut self, model: Arc<E>) -> Self {
+        self.energy_model = Some(model);
+        self
+    }
+
+    /// The rate model (default: `Arrhenius` with k0 = 1e5 at the
+    /// temperature of the energy model). Shift moves are enabled
+    /// if k3ws or k4ws are set.
+    pub fn rate_model(mut self, model: Arrhenius) -> Self {
+        self.rate_model = Some(model);
+        self
+    }
+
+    /// The output times of the timeline.
+    pub fn times(mut self, times: &[f64]) -> Self {
+        self.times = times.to_vec();
+        self
+    }
+
+    /// The number of trajectories (default: 1).
+    pub fn num_sims(mut self, n: usize) -> Self {
+        self.num_sims = n;
+        self
+    }
+
+    /// Macrostate files, see `MacrostateRegistry::insert_files`.
+    pub fn macrostates(mut self, files: &[PathBuf]) -> Self {
+        self.macrostates = files.to_vec();
+        self
+    }
+
+    /// Trajectory i uses seed + i, see `run_ensemble_with`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Observables tracked by the timeline of `run`.
+    pub fn observables(mut self, observables: Vec<Observable>) -> Self {
+        self.observables = observables;
+        self
+    }
+
+    /// Names of macrostates that stop a trajectory, see `simulate_timeline`.
+    pub fn absorbing<S: AsRef<str>>(mut self, names: &[S]) -> Self {
+        self.absorbing = names.iter().map(|s| s.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Called with the index and all moves of every finished trajectory.
+    pub fn events<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(u64, &[Event]) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.events = Some(Arc::new(sink));
+        self
+    }
+
+    /// Called after every finished trajectory.
+    pub fn progress<F: Fn() + Send + Sync + 'static>(mut self, progress: F) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// The macrostate registry of the sequence, energy model and macrostate files.
+    pub fn registry(&self) -> Result<MacrostateRegistry<E>, String> {
+        let sequence = self.sequence.clone().ok_or("No sequence specified.")?;
+        let emodel = self.energy_model.clone().ok_or("No energy model specified.")?;
+        let mut registry = MacrostateRegistry::from((sequence, emodel));
+        registry.insert_files(&self.macrostates).map_err(|e| e.to_string())?;
+        Ok(registry)
+    }
+
+    /// Simulate all trajectories (in parallel) and return the timeline.
+    pub fn run(&self) -> Result<Timeline<E>, String> {
+        if self.times.is_empty() {
+            return Err("No output times specified.".to_string());
+        }
+        let registry = Arc::new(self.registry()?);
+        self.run_into(Timeline::with_observables(&self.times, registry, self.observables.clone()))
+    }
+
+    /// Simulate all trajectories (in parallel) and merge them into `master`,
+    /// which determines output times, macrostates and observables.
+    pub fn run_into(&self, master: Timeline<E>) -> Result<Timeline<E>, String> {
+        let sequence = self.sequence.clone().ok_or("No sequence specified.")?;
+        let emodel = self.energy_model.clone().ok_or("No energy model specified.")?;
+        let pairings = match &self.structure {
+            Some(db) => PairTable::try_from(db).map_err(|e| e.to_string())?,
+            None => PairTable::try_from(".".repeat(sequence.len()).as_str()).map_err(|e| e.to_string())?,
+        };
+        if pairings.len() != sequence.len() {
+            return Err(format!("Structure length {} does not match sequence length {}.",
+                    pairings.len(), sequence.len()));
+        }
+        let rmodel = self.rate_model
+            .unwrap_or_else(|| Arrhenius::new(emodel.temperature(), 1e5, None, None));
+
+        match (rmodel.k3ws().is_some(), rmodel.k4ws().is_some()) {
+            (false, false) => {
+                let moves = LoopNeighbors::try_from((sequence, &pairings, emodel, NoShift))?;
+                self.simulate_ensemble(&moves, &rmodel, master)
+            },
+            (true, false) => {
+                let moves = LoopNeighbors::try_from((sequence, &pairings, emodel, ThreeWayOnly))?;
+                self.simulate_ensemble(&moves, &rmodel, master)
+            },
+            (false, true) => {
+                let moves = LoopNeighbors::try_from((sequence, &pairings, emodel, FourWayOnly))?;
+                self.simulate_ensemble(&moves, &rmodel, master)
+            },
+            (true, true) => {
+                let moves = LoopNeighbors::try_from((sequence, &pairings, emodel, ThreeAndFour))?;
+                self.simulate_ensemble(&moves, &rmodel, master)
+            },
+        }
+    }
+
+    fn simulate_ensemble<W: Walker + Clone + Send + Sync>(
+        &self,
+        walker: &W,
+        rmodel: &Arrhenius,
+        master: Timeline<E>,
+    ) -> Result<Timeline<E>, String> {
+        let absorbing = self.absorbing.iter()
+            .map(|name| master.registry.index_of(name)
+                .ok_or_else(|| format!("Unknown absorbing macrostate '{}'.", name)))
+            .collect::<Result<Vec<usize>, String>>()?;
+        let hooks = EnsembleHooks {
+            absorbing,
+            events: self.events.clone(),
+            progress: self.progress.clone(),
+        };
+        run_ensemble_with(walker, rmodel, master, self.num_sims, self.seed, &hooks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use ff_energy::ViennaRNA;
+
+    #[test]
+    fn test_simulation() {
+        let msfile = std::env::temp_dir().join("ff_kinetics_simulation.ms");
+        fs::write(&msfile, ">hairpin\nGGGAAACCC\n(((...)))\n").unwrap();
+        let emodel = Arc::new(ViennaRNA::default());
+        let times = [0.0, 1e-4, 1e-2];
+
+        let timeline = Simulation::new()
+            .sequence(NucleotideVec::try_from("GGGAAACCC").unwrap())
+            .energy_model(Arc::clone(&emodel))
+            .times(&times)
+            .num_sims(20)
+            .macrostates(&[msfile])
+            .seed(5)
+            .run()
+            .unwrap();
+        assert_eq!(timeline.points.len(), 3);
+        assert_eq!(timeline.registry.len(), 2);
+        assert!(timeline.points.iter().all(|tp| tp.counter == 20));
+        assert_eq!(timeline.point(0).occupancy(0), 1.0);
+        assert!(timeline.point(2).occupancy(1) > 0.0);
+
+        let missing = Simulation::<ViennaRNA>::new().energy_model(emodel).times(&times).run();
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_simulation_hooks() {
+        let msfile = std::env::temp_dir().join("ff_kinetics_simulation_hooks.ms");
+        fs::write(&msfile, ">hairpin\nGGGAAACCC\n(((...)))\n").unwrap();
+        let times: Vec<f64> = (0..=20).map(|i| i as f64 * 1e-3).collect();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        let simulation = {
+            let (events, finished) = (Arc::clone(&events), Arc::clone(&finished));
+            Simulation::new()
+                .sequence(NucleotideVec::try_from("GGGAAACCC").unwrap())
+                .energy_model(Arc::new(ViennaRNA::default()))
+                .times(&times)
+                .num_sims(8)
+                .macrostates(&[msfile])
+                .absorbing(&["hairpin"])
+                .seed(3)
+                .events(move |idx, trajectory| {
+                    events.lock().unwrap().push((idx, trajectory.to_vec()));
+                    Ok(())
+                })
+                .progress(move || { finished.fetch_add(1, Ordering::Relaxed); })
+        };
+        let timeline = simulation.run().unwrap();
+        assert_eq!(finished.load(Ordering::Relaxed), 8);
+
+        // Trajectories never leave the absorbing hairpin.
+        let hairpin = timeline.registry.index_of("hairpin").unwrap();
+        assert!(timeline.points.windows(2).all(|w| w[0].count(hairpin) <= w[1].count(hairpin)));
+        assert!(timeline.points.last().unwrap().count(hairpin) > 0);
+        let mut events = events.lock().unwrap();
+        events.sort_by_key(|(idx, _)| *idx);
+        assert!(events.iter().map(|(idx, _)| *idx).eq(0..8));
+        for (_, trajectory) in events.iter() {
+            assert!(trajectory.windows(2).all(|w| w[0].time < w[1].time));
+            // (Only the last move may overshoot t_max.)
+            assert!(trajectory.iter().rev().skip(1).all(|e| e.time < 2e-2));
+        }
+
+        drop(events);
+
+        // Extending an existing timeline adds to its counts.
+        let extended = simulation.run_into(timeline).unwrap();
+        assert_eq!(extended.n_trajectories(), 16);
+        assert_eq!(finished.load(Ordering::Relaxed), 16);
+
+        let unknown = Simulation::new()
+            .sequence(NucleotideVec::try_from("GGGAAACCC").unwrap())
+            .energy_model(Arc::new(ViennaRNA::default()))
+            .times(&times)
+            .absorbing(&["loop"])
+            .run();
+        assert!(unknown.is_err_and(|e| e.contains("loop")));
+    }
+}
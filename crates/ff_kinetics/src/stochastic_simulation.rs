@@ -1,11 +1,103 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use serde::{Serialize, Deserialize};
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
 use ff_structure::DotBracketVec;
+use ff_structure::PairTable;
+use ff_structure::PairSet;
 use rand::Rng; // -> R
+use ff_energy::EnergyModel;
+use rand::seq::SliceRandom;
 
 use crate::Walker;
+use crate::LoopNeighbors;
+use crate::shift_policy::ShiftPolicy;
+use crate::Move;
 use crate::Moves;
 use crate::RateModel; // -> K
+use crate::MacrostateRegistry;
+use crate::TransitionMatrix;
 use crate::rate_tree::RateTree;
 
+/// A set of forbidden structures (kinetic traps), looked up by their
+/// `PairSet` fingerprint. The monitor counts how many of the monitored
+/// trajectories visited at least one trap, and can be shared between
+/// threads to collect these counts across an ensemble.
+#[derive(Debug, Default)]
+pub struct TrapMonitor {
+    traps: FxHashSet<u64>,
+    trajectories: AtomicUsize,
+    trapped: AtomicUsize,
+}
+
+impl TrapMonitor {
+    pub fn new(traps: &[PairSet]) -> Self {
+        Self {
+            traps: traps.iter().map(|ps| ps.fingerprint()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_trap(&self, structure: &DotBracketVec) -> bool {
+        let pt = PairTable::try_from(structure).expect("Walkers produce well-formed structures");
+        self.traps.contains(&PairSet::from(&pt).fingerprint())
+    }
+
+    /// Number of monitored trajectories.
+    pub fn trajectories(&self) -> usize {
+        self.trajectories.load(Ordering::Relaxed)
+    }
+
+    /// Number of monitored trajectories that visited a trap.
+    pub fn trapped(&self) -> usize {
+        self.trapped.load(Ordering::Relaxed)
+    }
+
+    /// Fraction of monitored trajectories that visited a trap.
+    pub fn trapped_fraction(&self) -> f64 {
+        match self.trajectories() {
+            0 => 0.0,
+            n => self.trapped() as f64 / n as f64,
+        }
+    }
+}
+
+/// A move applied during a simulation, at absolute time `time`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    pub time: f64,
+    pub mv: Move,
+    /// Free energy change of the move (dcal/mol).
+    pub delta_e: i32,
+    /// The (natural) log rate of the move according to the RateModel.
+    pub log_rate: f64,
+}
+
+/// A snapshot of a running simulation, passed to the progress hook
+/// of `SSA::simulate_with_progress`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressInfo {
+    /// Number of moves applied so far.
+    pub steps: usize,
+    /// Current simulation time.
+    pub time: f64,
+    /// The sum of all rates of the current moves.
+    pub flux: f64,
+    /// The number of moves in the rate tree.
+    pub moves: usize,
+}
+
+/// The state of a simulator, see `SSA::snapshot` and `SSA::restore`.
+/// The moves are stored in the order of the rate tree, their rates are
+/// recomputed from the structure.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SsaSnapshot {
+    pub structure: String,
+    pub time: f64,
+    pub moves: Vec<Move>,
+}
+
 /// An SSA implementation for LoopStructure.
 pub struct SSA<W: Walker, K: RateModel> {
     /// The current RNA structure representation.
@@ -14,31 +106,61 @@ pub struct SSA<W: Walker, K: RateModel> {
     ratemodel: K,
     /// Heap-like data structure for sampling.
     rate_tree: RateTree,
+    /// Recompute all partial sums of the rate tree every N moves.
+    recompute_every: usize,
+    /// Rate tree updates since the partial sums were last recomputed.
+    updates: usize,
+    /// Total simulated time.
+    time: f64,
 }
 
 impl<W: Walker, K: RateModel> 
 From<(W, K)> for SSA<W, K>
 {
     fn from((walker, ratemodel): (W, K)) -> Self {
-        let mut rate_tree = RateTree::default();
-
-        for (mv, delta) in walker.propose_moves() {
-            let k = ratemodel.rate(&mv, delta);
-            if k > 0.0 {
-                rate_tree.init_insert(mv, k);
-            }
-        }
-        rate_tree.init_partial_sums();
-
-        Self {
+        let mut ssa = Self {
             walker,
             ratemodel,
-            rate_tree,
-        }
+            rate_tree: RateTree::default(),
+            recompute_every: 1024,
+            updates: 0,
+            time: 0.0,
+        };
+        ssa.init_rate_tree();
+        ssa
     }
 }
 
 impl<W: Walker, K: RateModel> SSA<W, K> {
+    /// The partial sums of the rate tree are updated along the path of every
+    /// changed move. As a safeguard, they are recomputed from scratch every 
+    /// `n` moves, counted across simulation calls (default: 1024, 0 = never).
+    pub fn with_recompute_every(mut self, n: usize) -> Self {
+        self.recompute_every = n;
+        self
+    }
+
+    /// Start over from the given structure, reusing the memory of the 
+    /// walker and the rate tree. 
+    pub fn reset(&mut self, initial: &PairTable) -> Result<(), String> {
+        self.walker.reset(initial)?;
+        self.time = 0.0;
+        self.rate_tree.clear();
+        self.init_rate_tree();
+        Ok(())
+    }
+
+    fn init_rate_tree(&mut self) {
+        for (mv, delta) in self.walker.propose_moves() {
+            let k = self.ratemodel.rate(&mv, delta);
+            if k > 0.0 {
+                self.rate_tree.init_insert(mv, k);
+            }
+        }
+        self.rate_tree.init_partial_sums();
+        self.updates = 0;
+    }
+
     pub fn current_structure(&self) -> DotBracketVec {
         self.walker.current_structure()
     }   
@@ -47,6 +169,31 @@ impl<W: Walker, K: RateModel> SSA<W, K> {
         self.walker.current_energy()
     }   
 
+    /// The total simulated time of all simulation calls since
+    /// construction (or the last reset).
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// The current structure, time and order of moves in the rate tree.
+    pub fn snapshot(&self) -> SsaSnapshot {
+        SsaSnapshot {
+            structure: self.walker.current_structure().to_string(),
+            time: self.time,
+            moves: self.rate_tree.iter().map(|(mv, _)| mv).collect(),
+        }
+    }
+
+    /// The sum of all rates of the current moves.
+    pub fn total_flux(&self) -> f64 {
+        self.rate_tree.total_rate()
+    }
+
+    /// The rates of all current moves.
+    pub fn rate_tree(&self) -> &RateTree {
+        &self.rate_tree
+    }
+
     pub fn co_simulate<R, F>(
         &mut self,
         rng: &mut R,
@@ -132,6 +279,12 @@ impl<W: Walker, K: RateModel> SSA<W, K> {
                 (None, None) => unreachable!(),
             }
         }
+
+        self.updates += 1;
+        if self.recompute_every > 0 && self.updates >= self.recompute_every {
+            self.rate_tree.init_partial_sums();
+            self.updates = 0;
+        }
     }
 
     /// Main simulation function.
@@ -139,11 +292,178 @@ impl<W: Walker, K: RateModel> SSA<W, K> {
         &mut self,
         rng: &mut R,
         t_max: f64,
+        callback: F,
+    ) -> bool
+    where
+        R: Rng + ?Sized,
+        F: FnMut(f64, f64, f64, &W) -> bool,
+    {
+        self.run(rng, t_max, callback, |_, _, _, _, _| {})
+    }
+
+    /// Same as `simulate`, but `progress` is invoked after every `every`
+    /// applied moves. The hook does not affect the sampling.
+    pub fn simulate_with_progress<R, F, P>(
+        &mut self,
+        rng: &mut R,
+        t_max: f64,
+        every: usize,
+        mut progress: P,
+        callback: F,
+    ) -> bool
+    where
+        R: Rng + ?Sized,
+        F: FnMut(f64, f64, f64, &W) -> bool,
+        P: FnMut(ProgressInfo),
+    {
+        assert!(every > 0, "progress interval must be positive");
+        let mut steps = 0;
+        self.run(rng, t_max, callback, |time, _, _, _, rate_tree| {
+            steps += 1;
+            if steps % every == 0 {
+                progress(ProgressInfo {
+                    steps,
+                    time,
+                    flux: rate_tree.total_rate(),
+                    moves: rate_tree.len(),
+                });
+            }
+        })
+    }
+
+    /// Same as `simulate`, but returns every applied move together
+    /// with the absolute time at which it happened.
+    pub fn simulate_recording<R, F>(
+        &mut self,
+        rng: &mut R,
+        t_max: f64,
+        callback: F,
+    ) -> Vec<Event>
+    where
+        R: Rng + ?Sized,
+        F: FnMut(f64, f64, f64, &W) -> bool,
+    {
+        let mut events = Vec::new();
+        let mut energy = self.walker.current_energy();
+        self.run(rng, t_max, callback, |time, mv, w, ratemodel, _| {
+            let delta_e = w.current_energy() - energy;
+            energy += delta_e;
+            events.push(Event { time, mv: *mv, delta_e, log_rate: ratemodel.log_rate(mv, delta_e) });
+        });
+        events
+    }
+
+    /// Runs the simulation until the structure equals `target` and
+    /// returns the first passage time, or None if t_max elapses first.
+    pub fn simulate_until<R>(
+        &mut self,
+        rng: &mut R,
+        t_max: f64,
+        target: &DotBracketVec,
+    ) -> Option<f64>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut hit = None;
+        self.simulate(rng, t_max, |t, _, _, w| {
+            if w.current_structure() == *target {
+                hit = Some(t);
+            }
+            hit.is_none()
+        });
+        hit
+    }
+
+    /// Same as `simulate`, but every visited structure is classified into a
+    /// macrostate of the registry. Transitions between different macrostates
+    /// and the residence time in each macrostate (up to t_max) are added to 
+    /// the transition matrix.
+    pub fn simulate_transitions<R, E>(
+        &mut self,
+        rng: &mut R,
+        t_max: f64,
+        registry: &MacrostateRegistry<E>,
+        transitions: &mut TransitionMatrix,
+    ) where
+        R: Rng + ?Sized,
+        E: EnergyModel,
+    {
+        let mut last = None;
+        self.simulate(rng, t_max, |t, tinc, _, w| {
+            let m_idx = registry.classify(&w.current_structure());
+            if let Some(prev) = last && prev != m_idx {
+                transitions.add_transition(prev, m_idx);
+            }
+            transitions.add_residence(m_idx, tinc.min(t_max - t));
+            last = Some(m_idx);
+            true
+        });
+    }
+
+    /// Approximate simulation with fixed time steps of length tau, where
+    /// every move fires at most once per step.
+    ///
+    /// In every window, each move fires with probability 1 - exp(-k*tau),
+    /// where k is its rate at the beginning of the window. This is not
+    /// tau-leaping: the number of firings is Bernoulli rather than Poisson
+    /// distributed, which is only accurate if k*tau is small for all moves.
+    /// The fired moves are applied in random order, and a move is skipped
+    /// if an earlier move of the same window has made it unavailable.
+    /// The callback is invoked once per window, with tinc = tau.
+    pub fn simulate_single_firing<R, F>(
+        &mut self,
+        rng: &mut R,
+        t_max: f64,
+        tau: f64,
         mut callback: F,
     ) -> bool
     where
         R: Rng + ?Sized,
         F: FnMut(f64, f64, f64, &W) -> bool,
+    {
+        assert!(tau > 0.0, "tau must be positive");
+        let mut t = 0.;
+        let mut fired = Vec::new();
+
+        while t < t_max {
+            let rsum = self.rate_tree.total_rate();
+            if !callback(t, tau, rsum, &self.walker) {
+                return false;
+            }
+            if rsum == 0.0 {
+                break;
+            }
+
+            fired.clear();
+            fired.extend(self.rate_tree.iter()
+                .filter(|&(_, k)| rng.random::<f64>() < -(-k * tau).exp_m1())
+                .map(|(mv, _)| mv));
+            fired.shuffle(rng);
+
+            for mv in &fired {
+                if self.rate_tree.contains(mv) {
+                    let (old, new) = self.walker.apply_move(mv);
+                    self.update_rate_tree(old, new);
+                }
+            }
+            t += tau;
+            self.time += tau;
+        }
+        true
+    }
+
+    /// The simulation loop, `on_move` is called after every applied move.
+    fn run<R, F, G>(
+        &mut self,
+        rng: &mut R,
+        t_max: f64,
+        mut callback: F,
+        mut on_move: G,
+    ) -> bool
+    where
+        R: Rng + ?Sized,
+        F: FnMut(f64, f64, f64, &W) -> bool,
+        G: FnMut(f64, &Move, &W, &K, &RateTree),
     {
         let mut t = 0.;
         let mut cb = true;
@@ -172,9 +492,71 @@ impl<W: Walker, K: RateModel> SSA<W, K> {
             let mv = self.rate_tree.select_by_threshold(threshold).expect("Must select a move!");
             let (old, new) = self.walker.apply_move(&mv);
             self.update_rate_tree(old, new);
+            on_move(t, &mv, &self.walker, &self.ratemodel, &self.rate_tree);
         }
+        self.time += t;
         cb
     }
+
+    /// Same as `simulate`, but every visited structure is checked against
+    /// the traps of the monitor. Returns true if the trajectory visited a trap.
+    /// The simulation is not interrupted by visiting a trap.
+    pub fn simulate_monitored<R, F>(
+        &mut self,
+        rng: &mut R,
+        t_max: f64,
+        monitor: &TrapMonitor,
+        mut callback: F,
+    ) -> bool
+    where
+        R: Rng + ?Sized,
+        F: FnMut(f64, f64, f64, &W) -> bool,
+    {
+        let mut trapped = false;
+        self.simulate(rng, t_max, |t, tinc, rsum, w| {
+            if !trapped && monitor.is_trap(&w.current_structure()) {
+                trapped = true;
+            }
+            callback(t, tinc, rsum, w)
+        });
+        monitor.trajectories.fetch_add(1, Ordering::Relaxed);
+        if trapped {
+            monitor.trapped.fetch_add(1, Ordering::Relaxed);
+        }
+        trapped
+    }
+}
+
+impl<E: EnergyModel, P: ShiftPolicy, K: RateModel> SSA<LoopNeighbors<E, P>, K> {
+    /// Continue from a snapshot: the walker is rebuilt from the structure
+    /// (same sequence, energy model and shift policy) and the rate tree is
+    /// rebuilt in the order of the snapshot. Together with the state of the
+    /// random number generator, the simulation continues the same trajectory.
+    pub fn restore(&mut self, snapshot: &SsaSnapshot) -> Result<(), String> {
+        let pt = PairTable::try_from(snapshot.structure.as_str()).map_err(|e| e.to_string())?;
+        let walker = self.walker.with_structure(&pt)?;
+        let mut available: FxHashMap<Move, i32> = walker.propose_moves().collect();
+
+        let mut rate_tree = RateTree::default();
+        for mv in &snapshot.moves {
+            let delta = available.remove(mv)
+                .ok_or_else(|| format!("Move {:?} of the snapshot is not available.", mv))?;
+            let k = self.ratemodel.rate(mv, delta);
+            if k > 0.0 {
+                rate_tree.init_insert(*mv, k);
+            }
+        }
+        if let Some((mv, _)) = available.iter().find(|&(mv, &d)| self.ratemodel.rate(mv, d) > 0.0) {
+            return Err(format!("Move {:?} is missing in the snapshot.", mv));
+        }
+        rate_tree.init_partial_sums();
+
+        self.walker = walker;
+        self.rate_tree = rate_tree;
+        self.updates = 0;
+        self.time = snapshot.time;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -287,6 +669,314 @@ mod tests {
                 true
         });
     }
-}
 
+    #[test]
+    fn test_trap_monitor() {
+        // The hairpin trap needs at least two moves from the open chain.
+        let trap = PairSet::from(&PairTable::try_from("((....))").unwrap());
+        let monitor = TrapMonitor::new(&[trap]);
+
+        setup_ssa_input!(walker, rmodel, "GCGAAAGC", "........");
+        let mut simulator = SSA::from((walker, rmodel));
+        let mut first_visit = None;
+        let mut steps = 0;
+        assert!(simulator.simulate_monitored(&mut StdRng::seed_from_u64(42), 1e3, &monitor, |_, _, _, w| {
+            if first_visit.is_none() && monitor.is_trap(&w.current_structure()) {
+                first_visit = Some(steps);
+            }
+            steps += 1;
+            true
+        }));
+        assert!(first_visit.unwrap() >= 2);
+        assert_eq!((monitor.trajectories(), monitor.trapped()), (1, 1));
+
+        // Control: stopped after a single move, which forms at most one pair.
+        setup_ssa_input!(walker, rmodel, "GCGAAAGC", "........");
+        let mut simulator = SSA::from((walker, rmodel));
+        let mut steps = 0;
+        assert!(!simulator.simulate_monitored(&mut StdRng::seed_from_u64(42), 1e3, &monitor, |_, _, _, _| {
+            steps += 1;
+            steps < 2
+        }));
+        assert_eq!(steps, 2);
+        assert_eq!((monitor.trajectories(), monitor.trapped()), (2, 1));
+        assert_eq!(monitor.trapped_fraction(), 0.5);
+    }
+
+    #[test]
+    fn test_simulate_recording() {
+        setup_ssa_input!(walker, rmodel, "GGGAAACCCAGGGAAAACCCA", ".....................");
+        let mut simulator = SSA::from((walker, rmodel));
+        let e_start = simulator.current_energy();
+
+        let events = simulator.simulate_recording(&mut StdRng::seed_from_u64(42), 100.0, |_, _, _, _| true);
+        assert!(!events.is_empty());
+        assert!(events.windows(2).all(|w| w[0].time <= w[1].time));
+        assert_eq!(events.iter().map(|e| e.delta_e).sum::<i32>(), simulator.current_energy() - e_start);
+        for e in &events {
+            assert_eq!(e.log_rate, rmodel.log_rate(&e.mv, e.delta_e));
+        }
+    }
+
+    #[test]
+    fn test_simulate_with_progress() {
+        let num_steps = 1000;
+        setup_ssa_input!(walker, rmodel, "GGGAAACCCAGGGAAAACCCA", ".....................");
+        let mut simulator = SSA::from((walker, rmodel));
+        let mut reports = Vec::new();
+        let mut calls = 0;
+        simulator.simulate_with_progress(&mut StdRng::seed_from_u64(42), f64::INFINITY, 100,
+            |info| reports.push(info),
+            |_, _, _, _| {
+                calls += 1;
+                calls <= num_steps
+            });
+        assert_eq!(reports.len(), 10);
+        assert!(reports.iter().enumerate().all(|(i, r)| r.steps == 100 * (i + 1)));
+        assert!(reports.windows(2).all(|w| w[0].time < w[1].time));
+        let last = reports.last().unwrap();
+        assert_eq!(last.flux, simulator.total_flux());
+        assert_eq!(last.moves, simulator.rate_tree().len());
+
+        // The hook does not change the trajectory.
+        setup_ssa_input!(walker, rmodel, "GGGAAACCCAGGGAAAACCCA", ".....................");
+        let mut plain = SSA::from((walker, rmodel));
+        plain.simulate(&mut StdRng::seed_from_u64(42), last.time, |_, _, _, _| true);
+        assert_eq!(plain.current_structure(), simulator.current_structure());
+    }
+
+    #[test]
+    fn test_clamped_waiting_times() {
+        // A flux of at most max_rate per move bounds the mean waiting time from below.
+        let (seq, db) = ("GGGAAACCCAGGGAAAACCCA", ".....................");
+        let max_rate = 10.0;
+        let n_max = seq.len() * seq.len();
+        let mean_waiting_time = |simulator: &mut SSA<_, _>| {
+            let (mut sum, mut steps) = (0.0, 0);
+            simulator.simulate(&mut StdRng::seed_from_u64(42), f64::INFINITY, |_, tinc, rsum, _| {
+                assert!(rsum <= n_max as f64 * max_rate);
+                sum += tinc;
+                steps += 1;
+                steps < 1000
+            });
+            sum / steps as f64
+        };
+
+        setup_ssa_input!(walker, _rmodel, seq, db);
+        let stiff = Arrhenius::new(37.0, 1e9, None, None);
+        let mut simulator = SSA::from((walker, crate::Clamped::new(stiff, None, Some(max_rate))));
+        let clamped = mean_waiting_time(&mut simulator);
+        assert!(clamped > 1.0 / (n_max as f64 * max_rate));
+
+        setup_ssa_input!(walker, _rmodel, seq, db);
+        let mut simulator = SSA::from((walker, stiff));
+        let mut unclamped = 0.0;
+        simulator.simulate(&mut StdRng::seed_from_u64(42), 1e-3, |_, tinc, _, _| {
+            unclamped = tinc;
+            false
+        });
+        assert!(unclamped < 1e-6 * clamped);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let (seq, db) = ("GGGAAACCCAGGGAAAACCCA", ".....................");
+        let continued = |simulator: &mut SSA<_, _>, rng: &mut StdRng| {
+            let events = simulator.simulate_recording(rng, 10.0, |_, _, _, _| true);
+            (events, simulator.current_structure(), simulator.time())
+        };
+
+        setup_ssa_input!(walker, rmodel, seq, db);
+        let mut uninterrupted = SSA::from((walker, rmodel));
+        let mut rng = StdRng::seed_from_u64(42);
+        uninterrupted.simulate(&mut rng, 10.0, |_, _, _, _| true);
+        let expected = continued(&mut uninterrupted, &mut rng);
+
+        setup_ssa_input!(walker, rmodel, seq, db);
+        let mut interrupted = SSA::from((walker, rmodel));
+        let mut rng = StdRng::seed_from_u64(42);
+        interrupted.simulate(&mut rng, 10.0, |_, _, _, _| true);
+        let json = serde_json::to_string(&interrupted.snapshot()).unwrap();
+        let snapshot: SsaSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot, interrupted.snapshot());
+        assert!(snapshot.time >= 10.0);
+
+        setup_ssa_input!(walker, rmodel, seq, db);
+        let mut resumed = SSA::from((walker, rmodel));
+        resumed.restore(&snapshot).unwrap();
+        assert_eq!(resumed.current_structure().to_string(), snapshot.structure);
+        assert_eq!(resumed.time(), snapshot.time);
+        assert!((resumed.total_flux() - interrupted.total_flux()).abs() < 1e-9 * interrupted.total_flux());
+        let actual = continued(&mut resumed, &mut rng);
+        assert!(!actual.0.is_empty());
+        assert_eq!(actual, expected);
+
+        let mut broken = snapshot.clone();
+        broken.moves.pop();
+        assert!(resumed.restore(&broken).is_err());
+    }
+
+    #[test]
+    fn test_simulate_until() {
+        let target = DotBracketVec::try_from("(...)").unwrap();
+        setup_ssa_input!(walker, rmodel, "GAAAC", ".....");
+        let mut simulator = SSA::from((walker, rmodel));
+        let t = simulator.simulate_until(&mut StdRng::seed_from_u64(42), 1e9, &target);
+        assert!(t.is_some_and(|t| t > 0.0 && t < 1e9));
+        assert_eq!(simulator.current_structure(), target);
+
+        // Starting in the target.
+        setup_ssa_input!(walker, rmodel, "GAAAC", "(...)");
+        let mut simulator = SSA::from((walker, rmodel));
+        assert_eq!(simulator.simulate_until(&mut StdRng::seed_from_u64(42), 1e9, &target), Some(0.0));
+
+        // An A-A pair is never formed.
+        let never = DotBracketVec::try_from(".(.).").unwrap();
+        setup_ssa_input!(walker, rmodel, "GAAAC", ".....");
+        let mut simulator = SSA::from((walker, rmodel));
+        assert_eq!(simulator.simulate_until(&mut StdRng::seed_from_u64(42), 1e5, &never), None);
+    }
+
+    #[test]
+    fn test_single_firing_against_ssa() {
+        // A small hairpin that folds and unfolds many times within t_max,
+        // such that time averages of single trajectories are meaningful.
+        let (seq, open) = ("GCGAAAGC", "........");
+        let hairpin = DotBracketVec::try_from("((....))").unwrap();
+        let t_max = 2e4;
+        let occupancy = |occ: &mut [f64; 2], w: &LoopNeighbors<ViennaRNA, shift_policy::NoShift>, dt: f64| {
+            let db = w.current_structure();
+            if db == hairpin {
+                occ[0] += dt / t_max;
+            } else if db.to_string() == open {
+                occ[1] += dt / t_max;
+            }
+        };
+
+        setup_ssa_input!(walker, rmodel, seq, open);
+        let mut simulator = SSA::from((walker, rmodel));
+        let mut exact = [0.0; 2];
+        simulator.simulate(&mut StdRng::seed_from_u64(42), t_max, |t, tinc, _, w| {
+            occupancy(&mut exact, w, tinc.min(t_max - t));
+            true
+        });
+
+        setup_ssa_input!(walker, rmodel, seq, open);
+        let mut simulator = SSA::from((walker, rmodel));
+        let mut single = [0.0; 2];
+        assert!(simulator.simulate_single_firing(&mut StdRng::seed_from_u64(42), t_max, 0.1, |_, tinc, _, w| {
+            occupancy(&mut single, w, tinc);
+            true
+        }));
+
+        assert!(exact[0] > 0.5 && exact[1] > 0.2);
+        for (e, s) in exact.iter().zip(&single) {
+            assert!((e - s).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_transition_matrix_chain() {
+        // Unassigned <-> partial <-> full: from the full hairpin, every 
+        // move leads into the partial macrostate.
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq, Arc::new(ViennaRNA::default())));
+        let input = b">chain\nGGGAAACCC\nfull (((...)))\npartial .((...)).\npartial (.(...).)\npartial ((.....))\n";
+        registry.insert_from_reader(std::io::Cursor::new(input), "chain").unwrap();
+        let (full, partial) = (registry.index_of("full").unwrap(), registry.index_of("partial").unwrap());
+
+        setup_ssa_input!(walker, rmodel, "GGGAAACCC", ".........");
+        let mut simulator = SSA::from((walker, rmodel));
+        let mut transitions = TransitionMatrix::new(registry.len());
+        simulator.simulate_transitions(&mut StdRng::seed_from_u64(42), 1e4, &registry, &mut transitions);
+
+        let rates = transitions.rates();
+        assert!((transitions.residence().iter().sum::<f64>() - 1e4).abs() < 1e-6);
+        assert!(rates[[full, partial]] > 0.0 && rates[[partial, full]] > 0.0);
+        assert!(rates[[0, partial]] > 0.0 && rates[[partial, 0]] > 0.0);
+        assert_eq!(rates[[full, 0]], 0.0);
+        assert_eq!(rates[[0, full]], 0.0);
+        assert!((0..3).all(|i| rates[[i, i]] == 0.0));
+    }
+
+    #[test]
+    fn test_closure_model() {
+        setup_ssa_input!(walker, _rmodel, "GGGAAACCCAGGGAAAACCCA", ".....................");
+        let mut simulator = SSA::from((walker, crate::from_fn(|_| 2.0)));
+        let events = simulator.simulate_recording(&mut StdRng::seed_from_u64(42), 10.0, |_, _, _, _| true);
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|e| e.log_rate == 2.0_f64.ln()));
+    }
+
+    #[test]
+    fn test_reset() {
+        setup_ssa_input!(walker, rmodel, "GGGAAACCCAGGGAAAACCCA", ".....................");
+        let trajectory = |simulator: &mut SSA<_, _>, seed| {
+            simulator.simulate_recording(&mut StdRng::seed_from_u64(seed), 10.0, |_, _, _, _| true)
+        };
+
+        let mut fresh = SSA::from((walker.clone(), rmodel));
+        let expected = trajectory(&mut fresh, 7);
+
+        let initial = PairTable::try_from(&walker.current_structure()).unwrap();
+        let mut reused = SSA::from((walker.clone(), rmodel));
+        trajectory(&mut reused, 3);
+        assert_ne!(reused.current_structure(), walker.current_structure());
+        reused.reset(&initial).unwrap();
+        assert_eq!(reused.current_structure(), walker.current_structure());
+        assert_eq!(reused.walker.current_energy(), walker.current_energy());
+        let moves = |w: &LoopNeighbors<_, _>| w.propose_moves().collect::<FxHashSet<_>>();
+        assert_eq!(moves(&reused.walker), moves(&walker));
+        assert_eq!(trajectory(&mut reused, 7), expected);
+        assert_eq!(reused.time(), fresh.time());
+
+        let too_long = PairTable::try_from(".".repeat(30).as_str()).unwrap();
+        assert!(reused.reset(&too_long).is_err());
+    }
+
+    #[test]
+    fn test_flux_accessors() {
+        setup_ssa_input!(walker, rmodel, "GGGAAACCCAGGGAAAACCCA", ".....................");
+        let mut simulator = SSA::from((walker, rmodel));
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..50 {
+            simulator.simulate(&mut rng, 0.5, |_, _, _, _| true);
+            let tree_sum: f64 = simulator.rate_tree().iter().map(|(_, k)| k).sum();
+            assert!((simulator.total_flux() - tree_sum).abs() < 1e-9 * tree_sum);
+            assert_eq!(simulator.rate_tree().len(), simulator.walker.propose_moves()
+                .filter(|(mv, d)| rmodel.rate(mv, *d) > 0.0).count());
+        }
+    }
 
+    #[test]
+    fn test_recompute_every() {
+        // The total rate of a tree rebuilt from scratch, in the same order.
+        let relative_error = |simulator: &SSA<_, _>| {
+            let mut fresh = RateTree::default();
+            for (mv, k) in simulator.rate_tree().iter() {
+                fresh.init_insert(mv, k);
+            }
+            fresh.init_partial_sums();
+            (simulator.total_flux() - fresh.total_rate()).abs() / fresh.total_rate()
+        };
+        // One move per call, so the counter must persist across calls.
+        let single_steps = |simulator: &mut SSA<_, _>, n: usize| {
+            let mut rng = StdRng::seed_from_u64(42);
+            for _ in 0..n {
+                let mut first = true;
+                simulator.simulate(&mut rng, f64::INFINITY, |_, _, _, _| std::mem::take(&mut first));
+            }
+        };
+
+        setup_ssa_input!(walker, rmodel, "GGGAAACCCAGGGAAAACCCA", ".....................");
+        let mut never = SSA::from((walker.clone(), rmodel)).with_recompute_every(0);
+        single_steps(&mut never, 20_500);
+        let mut every = SSA::from((walker, rmodel)).with_recompute_every(1000);
+        single_steps(&mut every, 20_500);
+
+        assert_eq!(never.current_structure(), every.current_structure());
+        assert_eq!((never.updates, every.updates), (20_500, 500));
+        assert!(relative_error(&never) < 1e-12);
+        assert!(relative_error(&every) < 1e-12);
+    }
+}
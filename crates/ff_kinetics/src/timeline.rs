@@ -1,7 +1,9 @@
 use std::fmt;
+use std::io;
 use std::sync::Arc;
 use std::error::Error;
 use nohash_hasher::IntMap;
+use rustc_hash::FxHashMap;
 
 use ff_energy::EnergyModel;
 use ff_structure::DotBracketVec; 
@@ -16,6 +18,8 @@ pub enum TimelineError {
     TimepointCountMismatch { found: usize, expected: usize },
     TimeMismatch { file_time: f64, expected_time: f64 },
     MacrostateNotFound(String),
+    InvalidCsv { line: usize, msg: String },
+    CountOverflow,
 }
 
 impl fmt::Display for TimelineError {
@@ -30,6 +34,10 @@ impl fmt::Display for TimelineError {
                 write!(f, "Time mismatch: {file_time} vs {expected_time}"),
             Self::MacrostateNotFound(name) =>
                 write!(f, "Macrostate '{name}' not found in registry"),
+            Self::InvalidCsv { line, msg } =>
+                write!(f, "Invalid CSV (line {line}): {msg}"),
+            Self::CountOverflow =>
+                write!(f, "Weighted trajectory counts exceed the integer range"),
         }
     }
 }
@@ -57,6 +65,37 @@ impl From<std::num::ParseFloatError> for TimelineError {
     fn from(e: std::num::ParseFloatError) -> Self { Self::Parse(e) } 
 }
 
+/// A named scalar property of a secondary structure (e.g. the number of
+/// base-pairs), tracked over time alongside the macrostate occupancies.
+#[derive(Clone)]
+pub struct Observable {
+    name: String,
+    func: Arc<dyn Fn(&DotBracketVec) -> f64 + Send + Sync>,
+}
+
+impl Observable {
+    pub fn new<F>(name: &str, func: F) -> Self
+    where
+        F: Fn(&DotBracketVec) -> f64 + Send + Sync + 'static,
+    {
+        Self { name: name.to_string(), func: Arc::new(func) }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn evaluate(&self, structure: &DotBracketVec) -> f64 {
+        (self.func)(structure)
+    }
+}
+
+impl fmt::Debug for Observable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observable").field("name", &self.name).finish()
+    }
+}
+
 /// One time point with its ensemble of macrostates.
 #[derive(Debug)]
 pub struct Timepoint {
@@ -66,6 +105,11 @@ pub struct Timepoint {
     pub ensemble: IntMap<usize, usize>,
     /// Total number of observations recorded at this timepoint
     pub counter: usize,
+    /// Sum of the squared weights of all observations. Equal to `counter`, 
+    /// unless the timepoint was rescaled by `Timeline::merge_weighted`.
+    pub weight_sq: f64,
+    /// Sum of each observable over all recorded structures
+    pub observed: Vec<f64>,
 }
 
 impl Timepoint {
@@ -75,6 +119,8 @@ impl Timepoint {
             time,
             ensemble: IntMap::default(),
             counter: 0,
+            weight_sq: 0.0,
+            observed: Vec::new(),
         }
     }
 
@@ -82,6 +128,7 @@ impl Timepoint {
     pub fn add(&mut self, macro_idx: usize) {
         *self.ensemble.entry(macro_idx).or_insert(0) += 1;
         self.counter += 1;
+        self.weight_sq += 1.0;
     }
 
     /// Get the count for a specific macrostate (or 0 if not present)
@@ -98,33 +145,133 @@ impl Timepoint {
         }
     }
 
+    /// The effective number of independent observations, 
+    /// counter^2 / weight_sq. (Equal to `counter` without weights.)
+    pub fn effective_size(&self) -> f64 {
+        if self.weight_sq > 0.0 {
+            (self.counter as f64).powi(2) / self.weight_sq
+        } else {
+            self.counter as f64
+        }
+    }
+
+    /// Standard error of the occupancy of a macrostate, where every
+    /// trajectory contributes one observation: sqrt(p * (1 - p) / n_eff).
+    pub fn stderr(&self, macro_idx: usize) -> f64 {
+        if self.counter == 0 {
+            0.0
+        } else {
+            let p = self.occupancy(macro_idx);
+            (p * (1.0 - p) / self.effective_size()).sqrt()
+        }
+    }
+
     /// Iterate over all macrostate counts
     pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
         self.ensemble.iter().map(|(k, v)| (*k, *v))
     }
 
+    /// Return the ensemble average of an observable (or 0 if nothing was recorded)
+    pub fn observable_mean(&self, obs_idx: usize) -> f64 {
+        if self.counter == 0 {
+            0.0
+        } else {
+            self.observed.get(obs_idx).copied().unwrap_or(0.0) / self.counter as f64
+        }
+    }
+
+    fn add_observed(&mut self, obs_idx: usize, value: f64) {
+        if self.observed.len() <= obs_idx {
+            self.observed.resize(obs_idx + 1, 0.0);
+        }
+        self.observed[obs_idx] += value;
+    }
 }
 
+/// A time, with name, mean occupancy and standard error of every macrostate.
+pub type OccupancyStats = (f64, Vec<(String, f64, f64)>);
+
 pub struct Timeline<E: EnergyModel> {
     /// Registry of all macrostates (used to classify structures)
     pub registry: Arc<MacrostateRegistry<E>>,
 
     /// One `Timepoint` per output time in the simulation
     pub points: Vec<Timepoint>,
+
+    /// Observables evaluated for every assigned structure
+    pub observables: Vec<Observable>,
+
+    /// Structures assigned at the last timepoint, with their counts
+    pub final_states: FxHashMap<DotBracketVec, usize>,
 }
 
 impl<E: EnergyModel> Timeline<E> {
     /// Build a new empty timeline for given times and an existing macrostate registry.
     pub fn new(times: &[f64], registry: Arc<MacrostateRegistry<E>>) -> Self {
+        Self::with_observables(times, registry, Vec::new())
+    }
+
+    /// Build a new empty timeline that additionally tracks the given observables.
+    pub fn with_observables(
+        times: &[f64], 
+        registry: Arc<MacrostateRegistry<E>>,
+        observables: Vec<Observable>,
+    ) -> Self {
         let points = times.iter().map(|&t| Timepoint::new(t)).collect();
-        Self { registry, points }
+        Self { registry, points, observables, final_states: FxHashMap::default() }
+    }
+
+    /// A new empty timeline with the same times, registry and observables.
+    pub fn empty_like(&self) -> Self {
+        let times: Vec<f64> = self.points.iter().map(|tp| tp.time).collect();
+        Self::with_observables(&times, Arc::clone(&self.registry), self.observables.clone())
     }
 
     /// Classify a structure and add it to the timeline at the given time index.
-    /// Returns `Some(macro_idx)` if classified, `None` if unclassified.
+    /// All observables are evaluated on the structure as well, and structures
+    /// assigned to the last timepoint are kept as final states.
     pub fn assign_structure(&mut self, t_idx: usize, structure: &DotBracketVec) {
         let m_idx = self.registry.classify(structure);
-        self.points[t_idx].add(m_idx);
+        let tp = &mut self.points[t_idx];
+        tp.add(m_idx);
+        for (o_idx, obs) in self.observables.iter().enumerate() {
+            tp.add_observed(o_idx, obs.evaluate(structure));
+        }
+        if t_idx + 1 == self.points.len() {
+            *self.final_states.entry(structure.clone()).or_insert(0) += 1;
+        }
+    }
+
+    /// Assign a structure to all timepoints from t_idx to the end, e.g. 
+    /// for a trajectory that was stopped in an absorbing macrostate.
+    pub fn assign_remaining(&mut self, t_idx: usize, structure: &DotBracketVec) {
+        for idx in t_idx..self.points.len() {
+            self.assign_structure(idx, structure);
+        }
+    }
+
+    /// The final structures of all trajectories, sorted by count (descending).
+    pub fn final_state_histogram(&self) -> Vec<(DotBracketVec, usize)> {
+        let mut hist: Vec<_> = self.final_states.iter()
+            .map(|(s, &c)| (s.clone(), c))
+            .collect();
+        hist.sort_by(|(s1, c1), (s2, c2)| 
+            c2.cmp(c1).then_with(|| s1.to_string().cmp(&s2.to_string())));
+        hist
+    }
+
+    /// For every timepoint: the time, and the name, mean occupancy and 
+    /// standard error of every macrostate. Each trajectory contributes a
+    /// 0/1 observation per macrostate, so the counts are sufficient to 
+    /// compute the variance (the sum of squares equals the count). For 
+    /// weighted timelines, the effective sample size is used.
+    pub fn occupancy_stats(&self) -> Vec<OccupancyStats> {
+        self.points.iter().map(|tp| {
+            let stats = self.registry.iter()
+                .map(|(idx, m)| (m.name().to_string(), tp.occupancy(idx), tp.stderr(idx)))
+                .collect();
+            (tp.time, stats)
+        }).collect()
     }
 
     /// Get a reference to a timepoint by index.
@@ -137,6 +284,15 @@ impl<E: EnergyModel> Timeline<E> {
         self.points.iter().enumerate()
     }
 
+    /// The number of trajectories assigned to the timeline. (Every 
+    /// trajectory is assigned to every timepoint.)
+    pub fn n_trajectories(&self) -> usize {
+        self.points.first().map_or(0, |tp| tp.counter)
+    }
+
+    /// Add the counts of another timeline. The occupancies are thus weighted 
+    /// by the number of trajectories of both timelines, which is the same as
+    /// `merge_weighted(other, self.n_trajectories(), other.n_trajectories())`.
     pub fn merge(&mut self, other: Timeline<E>) {
         assert!(
             Arc::ptr_eq(&self.registry, &other.registry),
@@ -144,17 +300,117 @@ impl<E: EnergyModel> Timeline<E> {
         );
         assert_eq!(self.points.len(), other.points.len(),
         "Cannot merge timelines with different numbers of timepoints");
+        assert!(
+            self.observables.iter().map(|o| o.name())
+                .eq(other.observables.iter().map(|o| o.name())),
+            "Cannot merge timelines with different observables"
+        );
 
         for (self_tp, other_tp) in self.points.iter_mut().zip(other.points) {
             for (macro_idx, count) in other_tp.iter() {
                 *self_tp.ensemble.entry(macro_idx).or_insert(0) += count;
             }
+            for (o_idx, &value) in other_tp.observed.iter().enumerate() {
+                self_tp.add_observed(o_idx, value);
+            }
             self_tp.counter += other_tp.counter;
+            self_tp.weight_sq += other_tp.weight_sq;
+        }
+        for (structure, count) in other.final_states {
+            *self.final_states.entry(structure).or_insert(0) += count;
+        }
+    }
+
+    /// Merge another timeline, such that the trajectories of this timeline
+    /// contribute `self_weight / (self_weight + other_weight)` to the 
+    /// occupancies and observables, and those of the other timeline the rest.
+    ///
+    /// Both timelines are multiplied by integer factors, so nothing is 
+    /// rounded: afterwards, `n_trajectories` is a (weighted) count that 
+    /// is in general larger than the number of simulated trajectories.
+    /// Standard errors use the effective sample size (see `Timepoint::weight_sq`).
+    /// If one of the timelines is empty, this is the same as `merge`.
+    ///
+    /// Repeated weighted merges multiply the counts, so they can exceed
+    /// the range of `usize`. In that case, `TimelineError::CountOverflow`
+    /// is returned and this timeline is left unchanged.
+    pub fn merge_weighted(
+        &mut self, 
+        other: Timeline<E>, 
+        self_weight: usize, 
+        other_weight: usize,
+    ) -> Result<(), TimelineError> {
+        assert!(self_weight > 0 || other_weight > 0, "At least one weight must be positive");
+        let (n_self, n_other) = (self.n_trajectories(), other.n_trajectories());
+        if n_self == 0 || n_other == 0 {
+            self.merge(other);
+            return Ok(());
+        }
+        // self_factor * n_self : other_factor * n_other = self_weight : other_weight
+        let a = self_weight.checked_mul(n_other).ok_or(TimelineError::CountOverflow)?;
+        let b = other_weight.checked_mul(n_self).ok_or(TimelineError::CountOverflow)?;
+        let g = gcd(a, b);
+        let (fa, fb) = (a / g, b / g);
+        // Counts never exceed the counter of their timepoint.
+        for (self_tp, other_tp) in self.points.iter().zip(&other.points) {
+            self_tp.counter.checked_mul(fa)
+                .zip(other_tp.counter.checked_mul(fb))
+                .and_then(|(x, y)| x.checked_add(y))
+                .ok_or(TimelineError::CountOverflow)?;
+        }
+        self.scale(fa);
+        self.merge(other.scaled(fb));
+        Ok(())
+    }
+
+    /// The same timeline, with all counts multiplied by `factor`.
+    fn scaled(mut self, factor: usize) -> Self {
+        self.scale(factor);
+        self
+    }
+
+    fn scale(&mut self, factor: usize) {
+        for tp in self.points.iter_mut() {
+            for count in tp.ensemble.values_mut() {
+                *count *= factor;
+            }
+            for value in tp.observed.iter_mut() {
+                *value *= factor as f64;
+            }
+            tp.counter *= factor;
+            tp.weight_sq *= (factor as f64).powi(2);
+        }
+        for count in self.final_states.values_mut() {
+            *count *= factor;
         }
+        self.final_states.retain(|_, count| *count > 0);
+    }
+
+    /// Write the ensemble averages of all observables as a table
+    /// (same layout as the occupancy table).
+    pub fn write_observables<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "{:>13}", "time")?;
+        for obs in &self.observables {
+            write!(writer, " {:>13}", obs.name())?;
+        }
+        writeln!(writer)?;
+
+        for tp in &self.points {
+            write!(writer, "{:13.6e}", tp.time)?;
+            for o_idx in 0..self.observables.len() {
+                write!(writer, " {:13.6e}", tp.observable_mean(o_idx))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
     }
 }
 
 
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
 impl<E: EnergyModel> fmt::Display for Timeline<E> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let macrostates = self.registry.macrostates();
@@ -178,3 +434,249 @@ impl<E: EnergyModel> fmt::Display for Timeline<E> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use ff_structure::PairTable;
+    use ff_energy::{NucleotideVec, ViennaRNA};
+    use ff_energy::parameters::RNA_TURNER_2004;
+    use crate::{SSA, Arrhenius, LoopNeighbors, Walker, shift_policy::NoShift};
+
+    #[test]
+    fn test_observable_number_of_pairs() {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let emodel = ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0);
+        let registry = Arc::new(MacrostateRegistry::from((seq, Arc::new(emodel))));
+
+        let pairs = Observable::new("pairs", |s: &DotBracketVec| {
+            s.0.iter().filter(|&&c| c == ff_structure::DotBracket::Open).count() as f64
+        });
+        let mut timeline = Timeline::with_observables(&[0.0, 1.0], registry, vec![pairs]);
+
+        let s0 = DotBracketVec::try_from(".........").unwrap();
+        let s1 = DotBracketVec::try_from("(((...)))").unwrap();
+        let s2 = DotBracketVec::try_from(".((...)).").unwrap();
+        timeline.assign_structure(0, &s0);
+        timeline.assign_structure(0, &s0);
+        timeline.assign_structure(1, &s1);
+        timeline.assign_structure(1, &s2);
+
+        assert_eq!(timeline.point(0).observable_mean(0), 0.0);
+        assert_eq!(timeline.point(1).observable_mean(0), 2.5);
+
+        let mut other = Timeline::with_observables(&[0.0, 1.0], 
+            Arc::clone(&timeline.registry), timeline.observables.clone());
+        other.assign_structure(1, &s0);
+        timeline.merge(other);
+        assert!((timeline.point(1).observable_mean(0) - 5.0 / 3.0).abs() < 1e-12);
+
+        let mut out = Vec::new();
+        timeline.write_observables(&mut out).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.lines().next().unwrap().contains("pairs"));
+        assert_eq!(table.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_final_state_histogram() {
+        let seq = NucleotideVec::try_from("GGGAAACCCAGGGAAACCC").unwrap();
+        let pt = PairTable::try_from("...................").unwrap();
+        let emodel = Arc::new(ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0));
+        let rmodel = Arrhenius::new(emodel.temperature(), 1e5, None, None);
+        let registry = Arc::new(MacrostateRegistry::from((seq.clone(), Arc::clone(&emodel))));
+        let walker = LoopNeighbors::try_from((seq, &pt, emodel, NoShift)).unwrap();
+
+        let times = [0.0, 1e-4, 1e-3];
+        let num_sims = 25;
+        let mut master = Timeline::new(&times, Arc::clone(&registry));
+        for seed in 0..num_sims {
+            let mut timeline = Timeline::new(&times, Arc::clone(&registry));
+            let mut simulator = SSA::from((walker.clone(), rmodel));
+            let mut t_idx = 0;
+            simulator.simulate(&mut StdRng::seed_from_u64(seed), times[2], |t, tinc, _, w| {
+                while t_idx < times.len() && t + tinc >= times[t_idx] {
+                    timeline.assign_structure(t_idx, &w.current_structure());
+                    t_idx += 1;
+                }
+                true
+            });
+            master.merge(timeline);
+        }
+
+        let hist = master.final_state_histogram();
+        assert_eq!(hist.iter().map(|(_, c)| c).sum::<usize>(), num_sims as usize);
+        assert!(hist.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
+    #[test]
+    fn test_absorbing_macrostate() {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let pt = PairTable::try_from(".........").unwrap();
+        let emodel = Arc::new(ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0));
+        let rmodel = Arrhenius::new(emodel.temperature(), 1e5, None, None);
+        let mut registry = MacrostateRegistry::from((seq.clone(), Arc::clone(&emodel)));
+        registry.insert_from_reader(std::io::Cursor::new(b">hairpin\nGGGAAACCC\n(((...)))\n"), "test").unwrap();
+        let registry = Arc::new(registry);
+        let absorbing = registry.index_of("hairpin").unwrap();
+        let walker = LoopNeighbors::try_from((seq, &pt, emodel, NoShift)).unwrap();
+
+        let times: Vec<f64> = (0..=100).map(|i| i as f64 * 1e-2).collect();
+        let mut timeline = Timeline::new(&times, Arc::clone(&registry));
+        let mut simulator = SSA::from((walker, rmodel));
+        let mut t_idx = 0;
+        let mut entered = None;
+        let completed = simulator.simulate(&mut StdRng::seed_from_u64(1), times[100], |t, tinc, _, w| {
+            let structure = w.current_structure();
+            if registry.classify(&structure) == absorbing {
+                entered = Some(t_idx);
+                timeline.assign_remaining(t_idx, &structure);
+                return false;
+            }
+            while t_idx < times.len() && t + tinc >= times[t_idx] {
+                timeline.assign_structure(t_idx, &structure);
+                t_idx += 1;
+            }
+            true
+        });
+
+        assert!(!completed);
+        let entered = entered.expect("The hairpin must be reached");
+        assert_eq!(simulator.current_structure(), DotBracketVec::try_from("(((...)))").unwrap());
+        assert!(timeline.points.iter().all(|tp| tp.counter == 1));
+        assert!(timeline.points[entered..].iter().all(|tp| tp.count(absorbing) == 1));
+    }
+
+    #[test]
+    fn test_occupancy_stats() {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let emodel = Arc::new(ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0));
+        let mut registry = MacrostateRegistry::from((seq, emodel));
+        registry.insert_from_reader(std::io::Cursor::new(b">hairpin\nGGGAAACCC\n(((...)))\n"), "test").unwrap();
+        let registry = Arc::new(registry);
+        let open = DotBracketVec::try_from(".........").unwrap();
+        let hairpin = DotBracketVec::try_from("(((...)))").unwrap();
+
+        let trajectory = |s0: &DotBracketVec, s1: &DotBracketVec| {
+            let mut timeline = Timeline::new(&[0.0, 1.0], Arc::clone(&registry));
+            timeline.assign_structure(0, s0);
+            timeline.assign_structure(1, s1);
+            timeline
+        };
+
+        let mut identical = trajectory(&open, &hairpin);
+        identical.merge(trajectory(&open, &hairpin));
+        for (_, stats) in identical.occupancy_stats() {
+            assert!(stats.iter().all(|&(_, _, se)| se == 0.0));
+        }
+
+        let mut divergent = trajectory(&open, &hairpin);
+        divergent.merge(trajectory(&open, &open));
+        let stats = divergent.occupancy_stats();
+        assert_eq!(stats.len(), 2);
+        assert!(stats[0].1.iter().all(|&(_, _, se)| se == 0.0));
+        let (time, ref last) = stats[1];
+        assert_eq!(time, 1.0);
+        assert_eq!(last[1].0, "hairpin");
+        assert_eq!(last[1].1, 0.5);
+        assert!(last[1].2 > 0.0);
+    }
+
+    #[test]
+    fn test_merge_weighted() {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let emodel = Arc::new(ViennaRNA::from_thermo_params(&RNA_TURNER_2004, 37.0));
+        let mut registry = MacrostateRegistry::from((seq, emodel));
+        registry.insert_from_reader(std::io::Cursor::new(b">hairpin\nGGGAAACCC\n(((...)))\n"), "test").unwrap();
+        let registry = Arc::new(registry);
+        let open = DotBracketVec::try_from(".........").unwrap();
+        let hairpin = DotBracketVec::try_from("(((...)))").unwrap();
+
+        // n trajectories, of which k end in the hairpin.
+        let batch = |n: usize, k: usize| {
+            let mut timeline = Timeline::new(&[0.0, 1.0], Arc::clone(&registry));
+            for i in 0..n {
+                timeline.assign_structure(0, &open);
+                timeline.assign_structure(1, if i < k { &hairpin } else { &open });
+            }
+            timeline
+        };
+
+        let mut plain = batch(3, 1);
+        plain.merge(batch(2, 2));
+        assert_eq!(plain.n_trajectories(), 5);
+        assert_eq!(plain.point(1).occupancy(1), 3.0 / 5.0);
+
+        let mut weighted = batch(3, 1);
+        weighted.merge_weighted(batch(2, 2), 9, 1).unwrap();
+        assert_eq!(weighted.n_trajectories(), 20);
+        assert!((weighted.point(1).occupancy(1) - (0.9 / 3.0 + 0.1)).abs() < 1e-12);
+        assert_eq!(weighted.point(0).occupancy(0), 1.0);
+        let hist = weighted.final_state_histogram();
+        assert_eq!(hist, vec![(open.clone(), 12), (hairpin.clone(), 8)]);
+
+        // Weights that are not multiples of the trajectory numbers.
+        let pairs = Observable::new("pairs", |s: &DotBracketVec| {
+            s.0.iter().filter(|&&c| c == ff_structure::DotBracket::Open).count() as f64
+        });
+        let observed = |n: usize, k: usize| {
+            let mut timeline = Timeline::with_observables(&[0.0, 1.0], 
+                Arc::clone(&registry), vec![pairs.clone()]);
+            for i in 0..n {
+                timeline.assign_structure(0, &open);
+                timeline.assign_structure(1, if i < k { &hairpin } else { &open });
+            }
+            timeline
+        };
+        for (w_self, w_other) in [(1, 1), (1, 2), (2, 1), (2, 3)] {
+            let mut weighted = observed(3, 1);
+            weighted.merge_weighted(observed(2, 2), w_self, w_other).unwrap();
+            let f = w_self as f64 / (w_self + w_other) as f64;
+            let p = f / 3.0 + (1.0 - f);
+            let tp = weighted.point(1);
+            assert!((tp.occupancy(1) - p).abs() < 1e-12);
+            assert!((tp.occupancy(0) - (1.0 - p)).abs() < 1e-12);
+            assert!((tp.observable_mean(0) - 3.0 * p).abs() < 1e-12);
+            assert!(weighted.final_state_histogram().iter().all(|(_, c)| *c > 0));
+            // The effective sample size never exceeds the 5 trajectories.
+            assert!(tp.effective_size() <= 5.0 + 1e-12);
+            let expected = (p * (1.0 - p) / tp.effective_size()).sqrt();
+            assert!((tp.stderr(1) - expected).abs() < 1e-12);
+            // The weighted counter would underestimate the error.
+            assert!(tp.stderr(1) > (p * (1.0 - p) / tp.counter as f64).sqrt());
+        }
+
+        // 3 trajectories (1 hairpin, 2 open) against 2 hairpins, with equal 
+        // weights: n_eff = (3*2 + 2*3)^2 / (3*4 + 2*9) = 4.8
+        let mut weighted = observed(3, 1);
+        weighted.merge_weighted(observed(2, 2), 1, 1).unwrap();
+        assert!((weighted.point(1).effective_size() - 4.8).abs() < 1e-12);
+        assert_eq!(weighted.point(1).count(1), 2 + 6);
+        assert_eq!(weighted.point(1).count(0), 4);
+
+        let mut empty = Timeline::new(&[0.0, 1.0], Arc::clone(&registry));
+        empty.merge_weighted(batch(2, 1), 5, 1).unwrap();
+        assert_eq!(empty.n_trajectories(), 2);
+        assert_eq!(empty.point(1).effective_size(), 2.0);
+
+        // Equal weights for batches with coprime sizes grow the counts
+        // until they overflow, which leaves the timeline unchanged.
+        let mut accumulated = batch(1000, 1);
+        let mut merged = 0;
+        for n in [999, 997, 991, 983, 977, 971, 967, 953, 947, 941, 937] {
+            let (n_before, p_before) = (accumulated.n_trajectories(), accumulated.point(1).occupancy(1));
+            match accumulated.merge_weighted(batch(n, 1), 1, 1) {
+                Ok(()) => merged += 1,
+                Err(e) => {
+                    assert!(matches!(e, TimelineError::CountOverflow));
+                    assert_eq!(accumulated.n_trajectories(), n_before);
+                    assert_eq!(accumulated.point(1).occupancy(1), p_before);
+                    break;
+                }
+            }
+        }
+        assert!(merged < 11, "Counts must overflow");
+    }
+}
@@ -1,4 +1,6 @@
 use std::fs;
+use std::io;
+use std::io::BufRead;
 use std::result;
 use std::sync::Arc;
 use std::path::Path;
@@ -21,6 +23,26 @@ pub struct SerializableTimePoint {
     time: f64,
     ensemble: Vec<(String, usize)>, // (macrostate name, count)
     counter: usize,
+    /// Only written for weighted timelines, see `Timepoint::weight_sq`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight_sq: Option<f64>,
+}
+
+/// Occupancy series of all macrostates at the output times of a timeline, 
+/// e.g. to be exported as JSON for plotting.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct OccupancySeries {
+    pub times: Vec<f64>,
+    pub macrostates: Vec<MacrostateSeries>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct MacrostateSeries {
+    pub name: String,
+    /// The ensemble free energy of the macrostate (None for "Unassigned").
+    pub energy: Option<f64>,
+    /// One occupancy value per output time.
+    pub occupancy: Vec<f64>,
 }
 
 fn same_time(a: f64, b: f64) -> bool {
@@ -41,11 +63,120 @@ impl<E: EnergyModel> Timeline<E> {
                     time: tp.time,
                     ensemble,
                     counter: tp.counter,
+                    weight_sq: (tp.weight_sq != tp.counter as f64).then_some(tp.weight_sq),
                 }
             }).collect()
         }
     }
 
+    /// The occupancy of every macrostate over time, with macrostate names and energies.
+    pub fn occupancy_series(&self) -> OccupancySeries {
+        OccupancySeries {
+            times: self.points.iter().map(|tp| tp.time).collect(),
+            macrostates: self.registry.iter().map(|(idx, m)| MacrostateSeries {
+                name: m.name().to_string(),
+                energy: m.ensemble_energy(),
+                occupancy: self.points.iter().map(|tp| tp.occupancy(idx)).collect(),
+            }).collect(),
+        }
+    }
+
+    /// The occupancy series as a JSON string, see `occupancy_series`.
+    pub fn to_json(&self) -> Result<String, TimelineError> {
+        Ok(serde_json::to_string_pretty(&self.occupancy_series())?)
+    }
+
+    /// Write the occupancies as CSV: a header `time,trajectories,<macrostates>`
+    /// and one row per timepoint. Values are written with full precision.
+    /// Weighted timelines (see `Timeline::merge_weighted`) have an additional
+    /// `weight_sq` column after `trajectories`. Macrostate names must not 
+    /// contain commas, as they are not quoted.
+    pub fn to_csv<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        if let Some((_, m)) = self.registry.iter().find(|(_, m)| m.name().contains(',')) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Macrostate name '{}' contains a comma", m.name())));
+        }
+        let weighted = self.points.iter().any(|tp| tp.weight_sq != tp.counter as f64);
+        write!(writer, "time,trajectories")?;
+        if weighted {
+            write!(writer, ",weight_sq")?;
+        }
+        for (_, m) in self.registry.iter() {
+            write!(writer, ",{}", m.name())?;
+        }
+        writeln!(writer)?;
+
+        for tp in &self.points {
+            write!(writer, "{},{}", tp.time, tp.counter)?;
+            if weighted {
+                write!(writer, ",{}", tp.weight_sq)?;
+            }
+            for (idx, _) in self.registry.iter() {
+                write!(writer, ",{}", tp.occupancy(idx))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Read a timeline from CSV, as written by `to_csv`. Columns are matched 
+    /// against the registry by name, macrostates without a column remain empty.
+    pub fn from_csv<R: io::Read>(
+        reader: R,
+        registry: Arc<MacrostateRegistry<E>>,
+    ) -> Result<Self, TimelineError> {
+        let invalid = |line: usize, msg: String| TimelineError::InvalidCsv { line, msg };
+        let mut lines = io::BufReader::new(reader).lines();
+
+        let header = lines.next().ok_or_else(|| invalid(1, "missing header".into()))??;
+        let cols: Vec<&str> = header.trim().split(',').collect();
+        if cols.len() < 2 || cols[0] != "time" || cols[1] != "trajectories" {
+            return Err(invalid(1, format!("expected 'time,trajectories,...', found '{}'", header)));
+        }
+        let weighted = cols.get(2) == Some(&"weight_sq");
+        let first = if weighted { 3 } else { 2 };
+        let map = cols[first..].iter().map(|&name| {
+            registry.iter()
+                .find(|(_, m)| m.name() == name)
+                .map(|(idx, _)| idx)
+                .ok_or_else(|| TimelineError::MacrostateNotFound(name.to_string()))
+        }).collect::<Result<Vec<usize>, _>>()?;
+
+        let mut timeline = Timeline::new(&[], registry);
+        for (l, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let values: Vec<&str> = line.trim().split(',').collect();
+            if values.len() != cols.len() {
+                return Err(invalid(l + 2, format!("expected {} columns, found {}", cols.len(), values.len())));
+            }
+            let time: f64 = values[0].parse()
+                .map_err(|_| invalid(l + 2, format!("invalid time '{}'", values[0])))?;
+            let mut tp = Timepoint::new(time);
+            let counter: usize = values[1].parse()
+                .map_err(|_| invalid(l + 2, format!("invalid trajectory count '{}'", values[1])))?;
+            for (&macro_idx, value) in map.iter().zip(&values[first..]) {
+                let occupancy: f64 = value.parse()
+                    .map_err(|_| invalid(l + 2, format!("invalid occupancy '{}'", value)))?;
+                let count = (occupancy * counter as f64).round() as usize;
+                if count > 0 {
+                    *tp.ensemble.entry(macro_idx).or_insert(0) += count;
+                }
+            }
+            tp.counter = counter;
+            tp.weight_sq = if weighted {
+                values[2].parse()
+                    .map_err(|_| invalid(l + 2, format!("invalid weight_sq '{}'", values[2])))?
+            } else {
+                counter as f64
+            };
+            timeline.points.push(tp);
+        }
+        Ok(timeline)
+    }
+
     /// Load a timeline from a JSON file, checking against the provided registry
     pub fn from_file<P: AsRef<std::path::Path>>(
         path: P,
@@ -82,6 +213,7 @@ impl<E: EnergyModel> Timeline<E> {
                     return Err(TimelineError::MacrostateNotFound(name));
                 }
             }
+            tp.weight_sq = serial_tp.weight_sq.unwrap_or(tp.counter as f64);
         }
         Ok(timeline)
     }
@@ -110,16 +242,18 @@ impl<E: EnergyModel> Timeline<E> {
                 self.points.len() - 1
             };
 
+            let tp = &mut self.points[t_idx];
+            let counter = tp.counter;
             for (name, count) in serial_tp.ensemble {
                 let macro_idx = self.registry.iter()
                     .find(|(_, m)| m.name() == name)
                     .map(|(idx, _)| idx)
                     .unwrap_or(0);
 
-                let tp = &mut self.points[t_idx];
                 *tp.ensemble.entry(macro_idx).or_insert(0) += count;
                 tp.counter += count;
             }
+            tp.weight_sq += serial_tp.weight_sq.unwrap_or((tp.counter - counter) as f64);
         }
         Ok(())
     }
@@ -193,6 +327,7 @@ impl<E: EnergyModel> Timeline<E> {
                     let tp = &mut self.points[t_idx];
                     *tp.ensemble.entry(macro_idx).or_insert(0) += count;
                     tp.counter += count;
+                    tp.weight_sq += count as f64;
                 }
             }
         }
@@ -206,4 +341,143 @@ impl<E: EnergyModel> Timeline<E> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use ff_energy::NucleotideVec;
+    use ff_energy::ViennaRNA;
+    use ff_structure::DotBracketVec;
+
+    #[test]
+    fn test_occupancy_json_round_trip() {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq, Arc::new(ViennaRNA::default())));
+        registry.insert_from_reader(Cursor::new(b">hairpin\nGGGAAACCC\n(((...)))\n"), "test").unwrap();
+        let times = [0.0, 0.5, 1.0];
+        let mut timeline = Timeline::new(&times, Arc::new(registry));
+
+        let open = DotBracketVec::try_from(".........").unwrap();
+        let hairpin = DotBracketVec::try_from("(((...)))").unwrap();
+        timeline.assign_structure(0, &open);
+        timeline.assign_structure(1, &open);
+        timeline.assign_structure(1, &hairpin);
+        timeline.assign_structure(2, &hairpin);
+
+        let json = timeline.to_json().unwrap();
+        let series: OccupancySeries = serde_json::from_str(&json).unwrap();
+        assert_eq!(series, timeline.occupancy_series());
+        assert_eq!(series.times, times);
+        assert_eq!(series.macrostates.len(), 2);
+        assert!(series.macrostates.iter().all(|m| m.occupancy.len() == times.len()));
+        assert_eq!(series.macrostates[0].name, "Unassigned");
+        assert_eq!(series.macrostates[0].energy, None);
+        assert_eq!(series.macrostates[1].name, "hairpin");
+        assert!(series.macrostates[1].energy.is_some());
+        assert_eq!(series.macrostates[1].occupancy, vec![0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_tln_keeps_weights() {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let registry = Arc::new(MacrostateRegistry::from((seq, Arc::new(ViennaRNA::default()))));
+        let times = [0.0, 1.0];
+        let open = DotBracketVec::try_from(".........").unwrap();
+        let batch = |n: usize| {
+            let mut timeline = Timeline::new(&times, Arc::clone(&registry));
+            for _ in 0..n {
+                timeline.assign_structure(0, &open);
+                timeline.assign_structure(1, &open);
+            }
+            timeline
+        };
+        let path = std::env::temp_dir().join("ff_kinetics_weighted.tln");
+
+        let mut weighted = batch(3);
+        weighted.merge_weighted(batch(2), 1, 1).unwrap();
+        fs::write(&path, serde_json::to_string(&weighted.to_serializable()).unwrap()).unwrap();
+        let loaded = Timeline::from_file(&path, &times, Arc::clone(&registry)).unwrap();
+        for (a, b) in weighted.points.iter().zip(&loaded.points) {
+            assert_eq!((a.counter, a.weight_sq), (b.counter, b.weight_sq));
+            assert!(b.effective_size() < b.counter as f64);
+        }
+
+        let plain = batch(4);
+        let json = serde_json::to_string(&plain.to_serializable()).unwrap();
+        assert!(!json.contains("weight_sq"));
+        fs::write(&path, json).unwrap();
+        let loaded = Timeline::from_file(&path, &times, registry).unwrap();
+        assert!(loaded.points.iter().all(|tp| tp.effective_size() == 4.0));
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq, Arc::new(ViennaRNA::default())));
+        registry.insert_from_reader(Cursor::new(b">hairpin\nGGGAAACCC\n(((...)))\n"), "test").unwrap();
+        let registry = Arc::new(registry);
+        let times = [0.0, 1e-3, 0.1, 1.0];
+        let mut timeline = Timeline::new(&times, Arc::clone(&registry));
 
+        let open = DotBracketVec::try_from(".........").unwrap();
+        let hairpin = DotBracketVec::try_from("(((...)))").unwrap();
+        for (t_idx, n_hairpin) in [(0, 0), (1, 1), (2, 2), (3, 6)] {
+            for k in 0..7 {
+                timeline.assign_structure(t_idx, if k < n_hairpin { &hairpin } else { &open });
+            }
+        }
+
+        let mut csv = Vec::new();
+        timeline.to_csv(&mut csv).unwrap();
+        let loaded = Timeline::from_csv(Cursor::new(&csv), Arc::clone(&registry)).unwrap();
+        assert_eq!(loaded.points.len(), times.len());
+        for (a, b) in timeline.points.iter().zip(&loaded.points) {
+            assert_eq!(a.time, b.time);
+            assert_eq!(a.counter, b.counter);
+            for m_idx in 0..registry.len() {
+                assert_eq!(a.occupancy(m_idx), b.occupancy(m_idx));
+            }
+        }
+
+        assert!(loaded.points.iter().all(|tp| tp.weight_sq == tp.counter as f64));
+
+        let unknown = b"time,trajectories,Unassigned,loop\n0,1,1,0\n";
+        let err = Timeline::from_csv(Cursor::new(unknown), Arc::clone(&registry)).err().unwrap();
+        assert!(matches!(err, TimelineError::MacrostateNotFound(name) if name == "loop"));
+
+        let short = b"time,trajectories,Unassigned,hairpin\n0,1,1\n";
+        let err = Timeline::from_csv(Cursor::new(short), Arc::clone(&registry)).err().unwrap();
+        assert!(matches!(err, TimelineError::InvalidCsv { line: 2, .. }));
+
+        let bad_time = b"time,trajectories,Unassigned,hairpin\n0,1,1,0\nx,1,1,0\n";
+        let err = Timeline::from_csv(Cursor::new(bad_time), Arc::clone(&registry)).err().unwrap();
+        assert!(matches!(err, TimelineError::InvalidCsv { line: 3, .. }));
+        let bad_value = b"time,trajectories,Unassigned,hairpin\n0,1,1,0.x\n";
+        let err = Timeline::from_csv(Cursor::new(bad_value), Arc::clone(&registry)).err().unwrap();
+        assert!(matches!(err, TimelineError::InvalidCsv { line: 2, .. }));
+
+        // Weighted timelines keep their effective sample size.
+        let mut weighted = timeline.empty_like();
+        weighted.merge(timeline);
+        let mut other = weighted.empty_like();
+        for t_idx in 0..times.len() {
+            other.assign_structure(t_idx, &hairpin);
+        }
+        weighted.merge_weighted(other, 1, 1).unwrap();
+        let mut csv = Vec::new();
+        weighted.to_csv(&mut csv).unwrap();
+        assert!(String::from_utf8_lossy(&csv).starts_with("time,trajectories,weight_sq,"));
+        let loaded = Timeline::from_csv(Cursor::new(&csv), Arc::clone(&registry)).unwrap();
+        for (a, b) in weighted.points.iter().zip(&loaded.points) {
+            assert_eq!((a.counter, a.weight_sq), (b.counter, b.weight_sq));
+            assert_eq!(a.stderr(1), b.stderr(1));
+        }
+
+        // Macrostate names with commas cannot be written.
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq, Arc::new(ViennaRNA::default())));
+        registry.insert_basin("hair,pin", &hairpin, 1).unwrap();
+        let timeline = Timeline::new(&times, Arc::new(registry));
+        assert!(timeline.to_csv(&mut Vec::new()).is_err());
+    }
+}
@@ -1,44 +1,150 @@
-use ff_energy::EnergyModel;
+use std::fmt;
+use std::error::Error;
 use std::path::Path;
+use ff_energy::EnergyModel;
 use plotters::prelude::*;
+use plotters::coord::Shift;
 use plotters::style::Palette99;
+use rustc_hash::FxHashMap;
 
 use crate::timeline::Timeline;
 
+/// Points of one occupancy curve: (time, occupancy, standard error).
+type Series = Vec<(f64, f64, f64)>;
+
+#[derive(Debug)]
+pub enum PlotError {
+    /// The file extension does not name a supported format (svg, png).
+    UnknownFormat(String),
+    /// The `PlotOptions` do not describe a valid time axis.
+    InvalidOptions(String),
+    Drawing(String),
+}
+
+impl fmt::Display for PlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat(ext) => 
+                write!(f, "Unknown plot format '{ext}' (expected .svg or .png)"),
+            Self::InvalidOptions(msg) => write!(f, "Invalid plot options: {msg}"),
+            Self::Drawing(e) => write!(f, "Plotting error: {e}"),
+        }
+    }
+}
+
+impl Error for PlotError {}
+
+impl<T: Error + Send + Sync> From<DrawingAreaErrorKind<T>> for PlotError {
+    fn from(e: DrawingAreaErrorKind<T>) -> Self { Self::Drawing(e.to_string()) }
+}
+
+/// Settings for occupancy plots.
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    pub title: String,
+    /// Line colors by macrostate name (others are taken from a palette).
+    pub colors: FxHashMap<String, RGBColor>,
+    /// Legend labels by macrostate name (default: name and ensemble energy).
+    pub labels: FxHashMap<String, String>,
+    pub line_width: u32,
+    /// If true, [0, t_lin] is shown on a linear and [t_lin, t_end] on a 
+    /// logarithmic axis, otherwise [0, t_end] is shown on a linear axis.
+    pub log_axis: bool,
+    pub t_lin: f64,
+    pub t_end: f64,
+}
+
+impl PlotOptions {
+    pub fn new(title: &str, t_lin: f64, t_end: f64) -> Self {
+        Self {
+            title: title.to_string(),
+            colors: FxHashMap::default(),
+            labels: FxHashMap::default(),
+            line_width: 2,
+            log_axis: true,
+            t_lin,
+            t_end,
+        }
+    }
+}
+
+/// Plot the occupancy of macrostates over time, with a linear panel 
+/// for [0, t_lin] and a logarithmic panel for [t_lin, t_log].
+/// The image format (SVG or PNG) is chosen by the extension of the filename.
 pub fn plot_occupancy_over_time<E: EnergyModel>(
     timeline: &Timeline<E>, 
     filename: impl AsRef<Path>,
     title: &str,
     t_lin: f64,
     t_log: f64,
-) {
-    assert!(t_lin > 0.0 && t_log > t_lin, "Require 0 < t_lin < t_log");
-
-    // Image size; tweak as you like
-    // let root = BitMapBackend::new(filename, (1024, 480)).into_drawing_area();
-    let root = SVGBackend::new(filename.as_ref(), (1024, 480)).into_drawing_area();
-    root.fill(&WHITE).unwrap();
-    root.titled(title, ("sans-serif", 28)).unwrap();
+) -> Result<(), PlotError> {
+    plot_occupancy_over_time_with(timeline, filename, &PlotOptions::new(title, t_lin, t_log))
+}
+
+/// Same as `plot_occupancy_over_time`, but with custom `PlotOptions`.
+pub fn plot_occupancy_over_time_with<E: EnergyModel>(
+    timeline: &Timeline<E>, 
+    filename: impl AsRef<Path>,
+    options: &PlotOptions,
+) -> Result<(), PlotError> {
+    let (valid, requirement) = if options.log_axis {
+        (options.t_lin > 0.0 && options.t_end > options.t_lin, "0 < t_lin < t_end")
+    } else {
+        (options.t_end > 0.0, "0 < t_end")
+    };
+    if !valid {
+        return Err(PlotError::InvalidOptions(format!("require {}, got t_lin = {}, t_end = {}", 
+            requirement, options.t_lin, options.t_end)));
+    }
+
+    let path = filename.as_ref();
+    let size = (1024, 480);
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match ext.to_ascii_lowercase().as_str() {
+        "svg" => draw_occupancy(timeline, SVGBackend::new(path, size).into_drawing_area(), options),
+        "png" => draw_occupancy(timeline, BitMapBackend::new(path, size).into_drawing_area(), options),
+        _ => Err(PlotError::UnknownFormat(ext.to_string())),
+    }
+}
+
+fn draw_occupancy<E: EnergyModel, DB: DrawingBackend>(
+    timeline: &Timeline<E>, 
+    root: DrawingArea<DB, Shift>,
+    options: &PlotOptions,
+) -> Result<(), PlotError> 
+where
+    DB::ErrorType: 'static,
+{
+    let t_end = options.t_end;
+    // The end of the linear panel.
+    let t_lin = if options.log_axis { options.t_lin } else { t_end };
+
+    root.fill(&WHITE)?;
+    root.titled(&options.title, ("sans-serif", 28))?;
     root.draw_text(
         "time",
         &("sans-serif", 22).into_font().into_text_style(&root),
         (496, 450), // roughly centered at bottom
-    ).unwrap();
-
+    )?;
 
     let eps = 1e-19; // epsilon for plot labels
     // Split into two panels: 50% for linear (left), 50% for log (right)
-    let (left, right) = root.split_horizontally(512);
+    let (left, right) = if options.log_axis {
+        let (left, right) = root.split_horizontally(512);
+        (left, Some(right))
+    } else {
+        (root.clone(), None)
+    };
 
     // ---- Left: linear panel ----
     let mut chart_left = ChartBuilder::on(&left)
         .caption("Linear plot", ("sans-serif", 18))
         .margin(20)
         .margin_top(40)
-        .margin_right(0)
+        .margin_right(if options.log_axis { 0 } else { 40 })
         .x_label_area_size(40)
         .y_label_area_size(50)
-        .build_cartesian_2d(0.0..(t_lin+eps), 0.0..1.0).unwrap();
+        .build_cartesian_2d(0.0..(t_lin+eps), 0.0..1.0)?;
     chart_left
         .configure_mesh()
         .y_desc("occupancy")
@@ -47,58 +153,53 @@ pub fn plot_occupancy_over_time<E: EnergyModel>(
         .light_line_style(RGBColor(220, 220, 220))
         .axis_desc_style(("sans-serif", 22))
         .label_style(("sans-serif", 18))
-        .draw()
-        .unwrap();
-
-    // draw separator at x = t_lin (right edge of this panel)
-    chart_left.draw_series(std::iter::once(PathElement::new(
-        vec![(t_lin, 0.0), (t_lin, 1.0)],
-        BLACK.mix(0.7),
-    ))).unwrap();
+        .draw()?;
 
     // ---- Right: log panel ----
-    let mut chart_right = ChartBuilder::on(&right)
-        .caption("Logarithmic plot", ("sans-serif", 18))
-        .margin(20)
-        .margin_top(40)
-        .margin_left(0)
-        .margin_right(40)
-        .x_label_area_size(40)
-        .y_label_area_size(0) // hide y labels on right
-        .build_cartesian_2d(((t_lin - eps)..(t_log + eps)).log_scale(), 0.0..1.0)
-        .unwrap();
+    let mut chart_right = match &right {
+        None => None,
+        Some(right) => {
+            // draw separator at x = t_lin (right edge of the linear panel)
+            chart_left.draw_series(std::iter::once(PathElement::new(
+                vec![(t_lin, 0.0), (t_lin, 1.0)],
+                BLACK.mix(0.7),
+            )))?;
 
-    chart_right
-        .configure_mesh()
-        .x_labels(6)
-        .x_label_formatter(&|x| if *x < 0.01 {format!("{:.1e}", x)} else {format!("{}", x)})  // scientific notation
-        .y_labels(10) // hide y ticks on right
-        .light_line_style(RGBColor(220, 220, 220))
-        .label_style(("sans-serif", 18))
-        .draw().unwrap();
+            let mut chart_right = ChartBuilder::on(right)
+                .caption("Logarithmic plot", ("sans-serif", 18))
+                .margin(20)
+                .margin_top(40)
+                .margin_left(0)
+                .margin_right(40)
+                .x_label_area_size(40)
+                .y_label_area_size(0) // hide y labels on right
+                .build_cartesian_2d(((t_lin - eps)..(t_end + eps)).log_scale(), 0.0..1.0)?;
 
-    // repeat separator at x = t_lin (left edge of this panel)
-    chart_right.draw_series(std::iter::once(PathElement::new(
-        vec![(t_lin, 0.0), (t_lin, 1.0)],
-        BLACK.mix(0.7),
-    ))).unwrap();
+            chart_right
+                .configure_mesh()
+                .x_labels(6)
+                .x_label_formatter(&|x| if *x < 0.01 {format!("{:.1e}", x)} else {format!("{}", x)})  // scientific notation
+                .y_labels(10) // hide y ticks on right
+                .light_line_style(RGBColor(220, 220, 220))
+                .label_style(("sans-serif", 18))
+                .draw()?;
 
+            // repeat separator at x = t_lin (left edge of this panel)
+            chart_right.draw_series(std::iter::once(PathElement::new(
+                vec![(t_lin, 0.0), (t_lin, 1.0)],
+                BLACK.mix(0.7),
+            )))?;
+            Some(chart_right)
+        }
+    };
 
     // Build data per structure
-    let mut trajectories: Vec<(usize, Vec<(f64, f64, f64)>)> = Vec::new();
+    let mut trajectories: Vec<(usize, Series)> = Vec::new();
 
     for (id, _) in timeline.registry.iter() {
-        let mut series = Vec::new();
-        for tp in &timeline.points {
-            let count = tp.ensemble.get(&id).copied().unwrap_or(0);
-            let occu = if tp.counter > 0 {
-                count as f64 / tp.counter as f64
-            } else { 0.0 };
-            let se = if tp.counter > 0 {
-                (occu * (1.0 - occu) / tp.counter as f64).sqrt()
-            } else { 0.0 };
-            series.push((tp.time, occu, se));
-        }
+        let series: Series = timeline.points.iter()
+            .map(|tp| (tp.time, tp.occupancy(id), tp.stderr(id)))
+            .collect();
         if id == 0 || series.iter().any(|(_, occu, _)| *occu >= 0.02) { // threshold filter
             trajectories.push((id, series));
         }
@@ -107,12 +208,18 @@ pub fn plot_occupancy_over_time<E: EnergyModel>(
     // Sort by ID to have consistent colors
     trajectories.sort_by_key(|(id, _)| *id);
 
-    // Find global Y max for normalization
     for (i, (id, series)) in trajectories.iter().enumerate() {
-        let color = Palette99::pick(i).mix(0.9); // pick a distinct color
-
-        let name = timeline.registry.macrostates()[*id].name();
-        let energy = timeline.registry.macrostates()[*id].ensemble_energy().unwrap_or(0.0);
+        let macrostate = &timeline.registry.macrostates()[*id];
+        let name = macrostate.name();
+        let color = match options.colors.get(name) {
+            Some(&c) => c.mix(0.9),
+            None => Palette99::pick(i).mix(0.9), // pick a distinct color
+        };
+        let label = match options.labels.get(name) {
+            Some(label) => label.clone(),
+            None => format!("{:20} {:>6.2}", name.trim(), macrostate.ensemble_energy().unwrap_or(0.0)),
+        };
+        let width = options.line_width;
 
         let z = 1.0; // or 1.96 for 95%
         let band_color = color.mix(0.2);
@@ -127,40 +234,124 @@ pub fn plot_occupancy_over_time<E: EnergyModel>(
                 .filter(|(t, _)| *t <= t_lin),
                 0.0,
                 band_color,
-        )).unwrap();
+        ))?;
 
-        chart_left.draw_series(LineSeries::new(
+        let line = chart_left.draw_series(LineSeries::new(
                 series.iter().cloned().map(|(t, p, _)| (t, p)).filter(|(t, _)| *t <= t_lin),
-                color.stroke_width(2),
-        )).unwrap();
+                color.stroke_width(width),
+        ))?;
 
-        chart_right.draw_series(AreaSeries::new(
-                upper
-                .filter(|(t, _)| *t >= t_lin),
-                0.0,
-                band_color,
-        )).unwrap();
- 
-        chart_right.draw_series(LineSeries::new(
-            series.iter().cloned().map(|(t, p, _)| (t, p)).filter(|(t, _)| *t >= t_lin),
-            color.stroke_width(2),
-        )).unwrap()
-            .label(format!("{:20} {:>6.2}", name.trim(), energy))   // <-- label for legend
+        let line = match chart_right.as_mut() {
+            None => line,
+            Some(chart_right) => {
+                chart_right.draw_series(AreaSeries::new(
+                        upper
+                        .filter(|(t, _)| *t >= t_lin),
+                        0.0,
+                        band_color,
+                ))?;
+         
+                chart_right.draw_series(LineSeries::new(
+                    series.iter().cloned().map(|(t, p, _)| (t, p)).filter(|(t, _)| *t >= t_lin),
+                    color.stroke_width(width),
+                ))?
+            }
+        };
+        line.label(label)   // <-- label for legend
             .legend(move |(x, y)| {
-                PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+                PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(width))
             });
     }
     
     // after loop:
-    chart_right
-        .configure_series_labels()
-        .border_style(BLACK)
-        .background_style(WHITE.mix(0.8))
-        .position(SeriesLabelPosition::UpperRight)
+    match chart_right.as_mut() {
+        Some(chart) => chart.configure_series_labels()
+            .border_style(BLACK)
+            .background_style(WHITE.mix(0.8))
+            .position(SeriesLabelPosition::UpperRight)
+            .label_font(("sans-serif", 16).into_font())   // <-- legend font size
+            .draw()?,
+        None => chart_left.configure_series_labels()
+            .border_style(BLACK)
+            .background_style(WHITE.mix(0.8))
+            .position(SeriesLabelPosition::UpperRight)
             .label_font(("sans-serif", 16).into_font())   // <-- legend font size
-        .draw().unwrap();
+            .draw()?,
+    }
     
-    root.present().unwrap(); // write the PNG
+    root.present()?; // write the file
+    Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use ff_energy::NucleotideVec;
+    use ff_energy::ViennaRNA;
+    use ff_structure::DotBracketVec;
+    use crate::MacrostateRegistry;
+
+    fn small_timeline() -> Timeline<ViennaRNA> {
+        let seq = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let mut registry = MacrostateRegistry::from((seq, Arc::new(ViennaRNA::default())));
+        registry.insert_from_reader(std::io::Cursor::new(b">hairpin\nGGGAAACCC\n(((...)))\n"), "test").unwrap();
+        let times = [0.0, 0.5, 1.0, 5.0, 10.0];
+        let mut timeline = Timeline::new(&times, Arc::new(registry));
+        let open = DotBracketVec::try_from(".........").unwrap();
+        let hairpin = DotBracketVec::try_from("(((...)))").unwrap();
+        for t_idx in 0..times.len() {
+            timeline.assign_structure(t_idx, &open);
+            timeline.assign_structure(t_idx, if t_idx > 1 { &hairpin } else { &open });
+        }
+        timeline
+    }
+
+    #[test]
+    fn test_plot_formats() {
+        let timeline = small_timeline();
+        let dir = std::env::temp_dir();
+
+        let png = dir.join("ff_kinetics_plot_test.png");
+        plot_occupancy_over_time(&timeline, &png, "test", 1.0, 10.0).unwrap();
+        let bytes = std::fs::read(&png).unwrap();
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        let svg = dir.join("ff_kinetics_plot_test.svg");
+        plot_occupancy_over_time(&timeline, &svg, "test", 1.0, 10.0).unwrap();
+        assert!(std::fs::read_to_string(&svg).unwrap().contains("<svg"));
 
+        let pdf = dir.join("ff_kinetics_plot_test.pdf");
+        assert!(matches!(plot_occupancy_over_time(&timeline, &pdf, "test", 1.0, 10.0),
+            Err(PlotError::UnknownFormat(ext)) if ext == "pdf"));
+        assert!(!pdf.exists());
+    }
+
+    #[test]
+    fn test_plot_with_options() {
+        let timeline = small_timeline();
+        let mut options = PlotOptions::new("custom", 1.0, 10.0);
+        options.colors.insert("hairpin".to_string(), RGBColor(200, 0, 0));
+        options.colors.insert("Unassigned".to_string(), RGBColor(0, 0, 200));
+        options.labels.insert("hairpin".to_string(), "closed".to_string());
+        options.line_width = 4;
+
+        let svg = std::env::temp_dir().join("ff_kinetics_plot_options.svg");
+        plot_occupancy_over_time_with(&timeline, &svg, &options).unwrap();
+        let content = std::fs::read_to_string(&svg).unwrap();
+        assert!(content.contains("closed"));
+        assert!(content.contains("#C80000"));
+
+        options.log_axis = false;
+        plot_occupancy_over_time_with(&timeline, &svg, &options).unwrap();
+        assert!(!std::fs::read_to_string(&svg).unwrap().contains("Logarithmic plot"));
+
+        options.t_end = 0.0;
+        assert!(matches!(plot_occupancy_over_time_with(&timeline, &svg, &options),
+            Err(PlotError::InvalidOptions(_))));
+        options.log_axis = true;
+        options.t_end = 0.5;
+        assert!(matches!(plot_occupancy_over_time_with(&timeline, &svg, &options),
+            Err(PlotError::InvalidOptions(_))));
+    }
+}
@@ -2,8 +2,11 @@ use std::fmt;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::convert::TryFrom;
+use std::str::FromStr;
 
 use crate::PairTable;
+use crate::Pair;
+use crate::PairSet;
 use crate::MultiPairTable;
 use crate::MultiStruct;
 use crate::StrandPairTable;
@@ -61,6 +64,63 @@ impl DerefMut for DotBracketVec {
     }
 }
 
+impl DotBracketVec {
+    fn check_length(&self, other: &DotBracketVec) -> Result<(), StructureError> {
+        if self.len() != other.len() {
+            return Err(StructureError::LengthMismatch(self.len(), other.len()));
+        }
+        Ok(())
+    }
+
+    /// Shared pairs, pairs of self, and pairs of other.
+    fn pair_counts(&self, other: &DotBracketVec) -> Result<(usize, usize, usize), StructureError> {
+        self.check_length(other)?;
+        let ps = PairSet::from(&PairTable::try_from(self)?);
+        let po = PairSet::from(&PairTable::try_from(other)?);
+        Ok((ps.intersection(&po).len(), ps.len(), po.len()))
+    }
+
+    /// Number of positions with differing dot-bracket symbols.
+    pub fn hamming_distance(&self, other: &DotBracketVec) -> Result<usize, StructureError> {
+        self.check_length(other)?;
+        Ok(self.iter().zip(other.iter()).filter(|(a, b)| a != b).count())
+    }
+
+    /// Number of base-pairs that are present in only one of the structures.
+    pub fn base_pair_distance(&self, other: &DotBracketVec) -> Result<usize, StructureError> {
+        let (shared, n_self, n_other) = self.pair_counts(other)?;
+        Ok(n_self + n_other - 2 * shared)
+    }
+
+    /// The base-pairs to delete (only in self) and to add (only in other)
+    /// in order to transform this structure into the other, each sorted.
+    pub fn moves_to(&self, other: &DotBracketVec) -> Result<(Vec<Pair>, Vec<Pair>), StructureError> {
+        self.check_length(other)?;
+        let ps = PairSet::from(&PairTable::try_from(self)?);
+        let po = PairSet::from(&PairTable::try_from(other)?);
+        let sorted = |set: PairSet| {
+            let mut pairs: Vec<Pair> = set.iter().collect();
+            pairs.sort_unstable();
+            pairs
+        };
+        Ok((sorted(ps.difference(&po)), sorted(po.difference(&ps))))
+    }
+
+    /// The fraction of reference pairs that are present in this (predicted)
+    /// structure. Returns 1.0 if the reference has no pairs.
+    pub fn sensitivity(&self, reference: &DotBracketVec) -> Result<f64, StructureError> {
+        let (shared, _, n_ref) = self.pair_counts(reference)?;
+        Ok(if n_ref == 0 { 1.0 } else { shared as f64 / n_ref as f64 })
+    }
+
+    /// The fraction of pairs in this (predicted) structure that are present
+    /// in the reference. Returns 1.0 if this structure has no pairs.
+    pub fn ppv(&self, reference: &DotBracketVec) -> Result<f64, StructureError> {
+        let (shared, n_pred, _) = self.pair_counts(reference)?;
+        Ok(if n_pred == 0 { 1.0 } else { shared as f64 / n_pred as f64 })
+    }
+}
+
 impl TryFrom<&str> for DotBracketVec {
     type Error = StructureError;
 
@@ -79,6 +139,14 @@ impl TryFrom<&str> for DotBracketVec {
     }
 }
 
+impl FromStr for DotBracketVec {
+    type Err = StructureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DotBracketVec::try_from(s)
+    }
+}
+
 impl From<&PairTable> for DotBracketVec {
     fn from(pt: &PairTable) -> Self {
         let mut result: Vec<DotBracket> = Vec::with_capacity(pt.len());
@@ -198,6 +266,30 @@ mod tests {
         assert_eq!(dbv[3], DotBracket::Unpaired);
     }
 
+    #[test]
+    fn test_dot_bracket_vec_parse() {
+        let dbv: DotBracketVec = "((..)).".parse().unwrap();
+        assert_eq!(dbv.to_string(), "((..)).");
+
+        let err = "(.x)".parse::<DotBracketVec>().unwrap_err();
+        assert!(matches!(err, StructureError::InvalidToken(tok, _, 2) if tok == "x"));
+    }
+
+    #[test]
+    fn test_moves_to() {
+        let nested = DotBracketVec::try_from("(())").unwrap();
+        let adjacent = DotBracketVec::try_from("()()").unwrap();
+        let (del, add) = nested.moves_to(&adjacent).unwrap();
+        assert_eq!(del, vec![Pair::new(0, 3), Pair::new(1, 2)]);
+        assert_eq!(add, vec![Pair::new(0, 1), Pair::new(2, 3)]);
+
+        let a = DotBracketVec::try_from("(.(..))").unwrap();
+        let b = DotBracketVec::try_from("((..).)").unwrap();
+        assert_eq!(a.moves_to(&b).unwrap(), (vec![Pair::new(2, 5)], vec![Pair::new(1, 4)]));
+        assert_eq!(a.moves_to(&a).unwrap(), (vec![], vec![]));
+        assert!(matches!(a.moves_to(&nested), Err(StructureError::LengthMismatch(7, 4))));
+    }
+
     #[test]
     fn test_dot_bracket_vec_from_pair_table() {
         let pt = PairTable::try_from("((..))").unwrap();
@@ -219,5 +311,28 @@ mod tests {
         assert_eq!(format!("{}", dbv), "((..)+)+");
     }
 
-}
 
+    #[test]
+    fn test_agreement_metrics() {
+        // The predicted helix contains one mispaired region: (1,10) became (1,9)
+        let reference = DotBracketVec::try_from("((((....))))").unwrap();
+        let predicted = DotBracketVec::try_from("((.(....)).)").unwrap();
+        assert_eq!(reference.hamming_distance(&reference).unwrap(), 0);
+        assert_eq!(predicted.hamming_distance(&reference).unwrap(), 2);
+        assert_eq!(predicted.base_pair_distance(&reference).unwrap(), 3);
+        assert_eq!(reference.base_pair_distance(&predicted).unwrap(), 3);
+
+        // 2 of 4 reference pairs are recovered, 2 of 3 predicted pairs are correct.
+        assert!((predicted.sensitivity(&reference).unwrap() - 2.0 / 4.0).abs() < 1e-12);
+        assert!((predicted.ppv(&reference).unwrap() - 2.0 / 3.0).abs() < 1e-12);
+
+        let open = DotBracketVec::try_from("............").unwrap();
+        assert_eq!(open.sensitivity(&reference).unwrap(), 0.0);
+        assert_eq!(open.ppv(&reference).unwrap(), 1.0);
+
+        let short = DotBracketVec::try_from("(((...)))").unwrap();
+        assert!(matches!(short.hamming_distance(&reference), Err(StructureError::LengthMismatch(9, 12))));
+        assert!(short.sensitivity(&reference).is_err());
+        assert!(short.ppv(&reference).is_err());
+    }
+}
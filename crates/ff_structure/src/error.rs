@@ -12,6 +12,9 @@ pub enum StructureError {
     UnmatchedClose(usize),
     UnmatchedMultiOpen((usize, usize)),
     UnmatchedMultiClose((usize, usize)),
+    LengthMismatch(usize, usize),
+    IndexOverflow(usize),
+    InvalidPair(usize, usize),
 }
 
 impl fmt::Display for StructureError {
@@ -32,6 +35,15 @@ impl fmt::Display for StructureError {
             StructureError::InvalidToken(tok, src, i) => {
                 write!(f, "Invalid {} in {} at position {}", tok, src, i)
             }
+            StructureError::LengthMismatch(a, b) => {
+                write!(f, "Structures of different length ({} vs {})", a, b)
+            }
+            StructureError::IndexOverflow(i) => {
+                write!(f, "Index {} exceeds the maximum index {}", i, crate::NAIDX::MAX - 1)
+            }
+            StructureError::InvalidPair(i, j) => {
+                write!(f, "Invalid pair ({}, {}), expected i < j", i, j)
+            }
         }
     }
 }
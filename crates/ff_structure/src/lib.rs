@@ -14,13 +14,19 @@ pub use pair_set::*;
 
 
 /// Nucleic Acid Index: we use `u16` (0 to 65k), which is plenty for nucleic acids.
-/// Should you ever want to fold longer sequences, beware that `P1KEY` needs to
-/// be *twice as large* (in bits) as `NAIDX`, since pairs `(NAIDX, NAIDX)` are
-/// compacted into one `P1KEY`.
+/// Should you ever want to fold longer sequences, enable the `wide-index` feature,
+/// which switches to `u32`. Beware that `P1KEY` needs to be *twice as large* 
+/// (in bits) as `NAIDX`, since pairs `(NAIDX, NAIDX)` are compacted into one `P1KEY`.
+#[cfg(not(feature = "wide-index"))]
 pub type NAIDX = u16;
+#[cfg(feature = "wide-index")]
+pub type NAIDX = u32;
 
 /// Pair key. Must be >= 2×`NAIDX` in bit width so we can safely pack two indices.
+#[cfg(not(feature = "wide-index"))]
 pub type P1KEY = u32;
+#[cfg(feature = "wide-index")]
+pub type P1KEY = u64;
 
 /// Compile-time sanity check: 2×NAIDX bits must fit into P1KEY.
 const _: () = {
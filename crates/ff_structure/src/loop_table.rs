@@ -1,7 +1,9 @@
 use std::fmt;
 use std::ops::Deref;
+use nohash_hasher::IntMap;
 
 use crate::NAIDX;
+use crate::Pair;
 use crate::PairTable;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -55,6 +57,176 @@ impl From<&PairTable> for LoopTable {
     }
 }
 
+/// Summary of a single loop in a `LoopTable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopDescriptor {
+    /// The loop id (0 is the exterior loop).
+    pub id: NAIDX,
+    /// The pair closing the loop (None for the exterior loop).
+    pub closing: Option<Pair>,
+    /// All positions in the loop, including paired ones, in ascending order.
+    pub members: Vec<NAIDX>,
+    /// Number of unpaired positions in the loop.
+    pub unpaired: usize,
+}
+
+/// The type of a loop, as distinguished by nearest neighbor energy models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopKind {
+    Exterior,
+    Hairpin,
+    /// Interior loop without unpaired positions.
+    Stack,
+    /// Interior loop with unpaired positions on one side only.
+    Bulge,
+    Interior,
+    Multibranch,
+}
+
+/// Classification of a single loop in a `LoopTable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopClass {
+    /// The loop id (0 is the exterior loop).
+    pub id: NAIDX,
+    /// The pair closing the loop (None for the exterior loop).
+    pub closing: Option<Pair>,
+    /// Number of enclosed helices.
+    pub branches: usize,
+    /// Number of unpaired positions in the loop.
+    pub unpaired: usize,
+    pub kind: LoopKind,
+}
+
+impl LoopTable {
+    /// Update the table for a newly formed pair, without rebuilding it.
+    /// The result is identical to `LoopTable::from` on the updated PairTable.
+    ///
+    /// Panics if the positions are not unpaired in the same loop.
+    pub fn apply_add(&mut self, pair: Pair) {
+        let (pi, pj) = (pair.i() as usize, pair.j() as usize);
+        let c = match (self.0[pi], self.0[pj]) {
+            (LoopInfo::Unpaired { l: a }, LoopInfo::Unpaired { l: b }) if a == b => a,
+            _ => panic!("Cannot add pair ({}, {}): positions are not unpaired in the same loop.", pi, pj),
+        };
+        // Loop ids follow the order of opening pairs, the new id therefore
+        // follows the last loop opened 5' of the new pair.
+        let new = 1 + self.0[..pi].iter().map(|info| match *info {
+            LoopInfo::Paired { i, .. } => i,
+            LoopInfo::Unpaired { .. } => 0,
+        }).max().unwrap_or(0);
+
+        let shift = |id: NAIDX| if id >= new { id + 1 } else { id };
+        for (k, info) in self.0.iter_mut().enumerate() {
+            let inside = pi < k && k < pj;
+            *info = match *info {
+                LoopInfo::Unpaired { l } if inside && l == c => LoopInfo::Unpaired { l: new },
+                LoopInfo::Unpaired { l } => LoopInfo::Unpaired { l: shift(l) },
+                LoopInfo::Paired { o, i } if inside && o == c => LoopInfo::Paired { o: new, i: shift(i) },
+                LoopInfo::Paired { o, i } => LoopInfo::Paired { o: shift(o), i: shift(i) },
+            };
+        }
+        self.0[pi] = LoopInfo::Paired { o: c, i: new };
+        self.0[pj] = LoopInfo::Paired { o: c, i: new };
+    }
+
+    /// Update the table for a removed pair, without rebuilding it.
+    /// The result is identical to `LoopTable::from` on the updated PairTable.
+    ///
+    /// Panics if the pair does not exist.
+    pub fn apply_del(&mut self, pair: Pair) {
+        let (pi, pj) = (pair.i() as usize, pair.j() as usize);
+        let (c, old) = match (self.0[pi], self.0[pj]) {
+            // Both ends of a pair (and only those) share the inner loop id.
+            (LoopInfo::Paired { o, i }, b) if b == self.0[pi] => (o, i),
+            _ => panic!("Cannot delete pair ({}, {}): not a base-pair.", pi, pj),
+        };
+
+        let shift = |id: NAIDX| if id > old { id - 1 } else { id };
+        for (k, info) in self.0.iter_mut().enumerate() {
+            let inside = pi < k && k < pj;
+            *info = match *info {
+                LoopInfo::Unpaired { l } if inside && l == old => LoopInfo::Unpaired { l: c },
+                LoopInfo::Unpaired { l } => LoopInfo::Unpaired { l: shift(l) },
+                LoopInfo::Paired { o, i } if inside && o == old => LoopInfo::Paired { o: c, i: shift(i) },
+                LoopInfo::Paired { o, i } => LoopInfo::Paired { o: shift(o), i: shift(i) },
+            };
+        }
+        self.0[pi] = LoopInfo::Unpaired { l: c };
+        self.0[pj] = LoopInfo::Unpaired { l: c };
+    }
+
+    /// Collect the members and the closing pair of every loop.
+    pub fn loops(&self) -> IntMap<NAIDX, LoopDescriptor> {
+        fn entry(loops: &mut IntMap<NAIDX, LoopDescriptor>, id: NAIDX) -> &mut LoopDescriptor {
+            loops.entry(id).or_insert_with(|| LoopDescriptor {
+                id,
+                closing: None,
+                members: Vec::new(),
+                unpaired: 0,
+            })
+        }
+
+        let mut loops = IntMap::default();
+
+        let mut open: IntMap<NAIDX, NAIDX> = IntMap::default();
+        for (k, info) in self.iter().enumerate() {
+            let k = k as NAIDX;
+            match *info {
+                LoopInfo::Unpaired { l } => {
+                    let lp = entry(&mut loops, l);
+                    lp.members.push(k);
+                    lp.unpaired += 1;
+                }
+                LoopInfo::Paired { o, i } => {
+                    entry(&mut loops, o).members.push(k);
+                    let lp = entry(&mut loops, i);
+                    lp.members.push(k);
+                    if let Some(p) = open.remove(&i) {
+                        lp.closing = Some(Pair::new(p, k));
+                    } else {
+                        open.insert(i, k);
+                    }
+                }
+            }
+        }
+        loops
+    }
+
+    /// Classify every loop by type and size, sorted by loop id.
+    pub fn classify_loops(&self) -> Vec<LoopClass> {
+        let mut classes: Vec<LoopClass> = self.loops().into_values().map(|lp| {
+            let paired = lp.members.len() - lp.unpaired;
+            let (branches, kind) = match lp.closing {
+                None => (paired / 2, LoopKind::Exterior),
+                Some(_) => {
+                    let branches = (paired - 2) / 2;
+                    // Unpaired positions between the closing pair and the first branch.
+                    let left = lp.members[1..].iter()
+                        .take_while(|&&k| matches!(self[k as usize], LoopInfo::Unpaired { .. }))
+                        .count();
+                    let kind = match branches {
+                        0 => LoopKind::Hairpin,
+                        1 if lp.unpaired == 0 => LoopKind::Stack,
+                        1 if left == 0 || left == lp.unpaired => LoopKind::Bulge,
+                        1 => LoopKind::Interior,
+                        _ => LoopKind::Multibranch,
+                    };
+                    (branches, kind)
+                }
+            };
+            LoopClass {
+                id: lp.id,
+                closing: lp.closing,
+                branches,
+                unpaired: lp.unpaired,
+                kind,
+            }
+        }).collect();
+        classes.sort_unstable_by_key(|c| c.id);
+        classes
+    }
+}
+
 impl fmt::Display for LoopTable {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut out = Vec::new();
@@ -72,6 +244,7 @@ impl fmt::Display for LoopTable {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::PairSet;
 
     #[test]
     fn test_loop_table_valid_structure() {
@@ -145,6 +318,117 @@ mod tests {
         assert_eq!(re, li); 
     }
 
+    #[test]
+    fn test_loops() {
+        let pt = PairTable::try_from(".(((...)).((...))..(.(...)))").unwrap();
+        let loops = LoopTable::from(&pt).loops();
+        assert_eq!(loops.len(), 8);
+
+        let ext = &loops[&0];
+        assert_eq!(ext.closing, None);
+        assert_eq!(ext.members, vec![0, 1, 27]);
+        assert_eq!(ext.unpaired, 1);
+
+        let ml = &loops[&1];
+        assert_eq!(ml.closing, Some(Pair::new(1, 27)));
+        assert_eq!(ml.members, vec![1, 2, 8, 9, 10, 16, 17, 18, 19, 26, 27]);
+        assert_eq!(ml.unpaired, 3);
+
+        let mut hairpins: Vec<_> = loops.values()
+            .filter(|lp| lp.closing.is_some() && lp.members.len() == lp.unpaired + 2)
+            .map(|lp| (lp.closing.unwrap(), lp.unpaired))
+            .collect();
+        hairpins.sort();
+        assert_eq!(hairpins, vec![
+            (Pair::new(3, 7), 3), 
+            (Pair::new(11, 15), 3), 
+            (Pair::new(21, 25), 3),
+        ]);
+    }
+
+    #[test]
+    fn test_classify_loops() {
+        let lt = LoopTable::from(&PairTable::try_from("((..))").unwrap());
+        assert_eq!(lt.classify_loops(), vec![
+            LoopClass { id: 0, closing: None, branches: 1, unpaired: 0, kind: LoopKind::Exterior },
+            LoopClass { id: 1, closing: Some(Pair::new(0, 5)), branches: 1, unpaired: 0, kind: LoopKind::Stack },
+            LoopClass { id: 2, closing: Some(Pair::new(1, 4)), branches: 0, unpaired: 2, kind: LoopKind::Hairpin },
+        ]);
+
+        let lt = LoopTable::from(&PairTable::try_from(".(((...)).((...))..(.(...)))").unwrap());
+        let kinds: Vec<_> = lt.classify_loops().iter().map(|c| (c.kind, c.branches, c.unpaired)).collect();
+        assert_eq!(kinds, vec![
+            (LoopKind::Exterior, 1, 1),
+            (LoopKind::Multibranch, 3, 3),
+            (LoopKind::Stack, 1, 0),
+            (LoopKind::Hairpin, 0, 3),
+            (LoopKind::Stack, 1, 0),
+            (LoopKind::Hairpin, 0, 3),
+            (LoopKind::Bulge, 1, 1),
+            (LoopKind::Hairpin, 0, 3),
+        ]);
+
+        let lt = LoopTable::from(&PairTable::try_from("(.(...)..)((...).)").unwrap());
+        let kinds: Vec<_> = lt.classify_loops().iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, vec![LoopKind::Exterior, LoopKind::Interior, LoopKind::Hairpin,
+            LoopKind::Bulge, LoopKind::Hairpin]);
+    }
+
+    #[test]
+    fn test_apply_add_del() {
+        let pt = PairTable::try_from(".(((...)).((...))..(.(...)))").unwrap();
+        let mut lt = LoopTable::from(&pt);
+
+        lt.apply_add(Pair::new(4, 6));
+        let expected = PairTable::try_from(".((((.))).((...))..(.(...)))").unwrap();
+        assert_eq!(lt, LoopTable::from(&expected));
+
+        lt.apply_del(Pair::new(1, 27));
+        let expected = PairTable::try_from("..(((.))).((...))..(.(...)).").unwrap();
+        assert_eq!(lt, LoopTable::from(&expected));
+    }
+
+    #[test]
+    #[should_panic(expected = "not unpaired in the same loop")]
+    fn test_apply_add_across_helix_panics() {
+        let pt = PairTable::try_from(".(((...)).((...))..(.(...)))").unwrap();
+        let mut lt = LoopTable::from(&pt);
+        lt.apply_add(Pair::new(0, 4));
+    }
+
+    /// Random add/del moves on a PairTable and LoopTable in parallel.
+    #[test]
+    fn test_apply_add_del_random_moves() {
+        // A small xorshift generator keeps the test deterministic.
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        let mut next = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as usize
+        };
+
+        let mut pt = PairTable::try_from(".(((...)).((...))..(.(...)))").unwrap();
+        let mut lt = LoopTable::from(&pt);
+        for _ in 0..2000 {
+            let adds = pt.addable_pairs();
+            let dels: Vec<Pair> = PairSet::from(&pt).to_vec();
+            let k = next(adds.len() + dels.len());
+            if k < adds.len() {
+                let p = adds[k];
+                pt[p.i()] = Some(p.j());
+                pt[p.j()] = Some(p.i());
+                lt.apply_add(p);
+            } else {
+                let p = dels[k - adds.len()];
+                pt[p.i()] = None;
+                pt[p.j()] = None;
+                lt.apply_del(p);
+            }
+            assert_eq!(lt, LoopTable::from(&pt));
+        }
+    }
+
     #[test]
     fn test_pair_table_to_loop_index_02() {
         use LoopInfo::*;
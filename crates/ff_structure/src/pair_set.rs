@@ -5,6 +5,7 @@
 //!
 //! A `Pair` is defined by two 16-bit indices (`NAIDX`) packed into a
 //! 32-bit integer key (`P1KEY`) for efficient set and map storage.
+//! (32-bit indices and 64-bit keys with the `wide-index` feature.)
 //!
 //! We currently do not povide the conversions from PairSet to 
 //! PairTable, mainly because at this stage it is not clear if
@@ -15,12 +16,15 @@ use std::fmt;
 use nohash_hasher::IntSet;
 
 use crate::PairTable;
+use crate::StructureError;
 use crate::NAIDX;
 use crate::P1KEY;
 
 
 /// A base pair (i, j) with i < j.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// Pairs are ordered lexicographically by `(i, j)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Pair {
     i: NAIDX,
     j: NAIDX,
@@ -34,6 +38,18 @@ impl Pair {
         Pair { i, j }
     }
 
+    /// Create a new pair (i, j), checking that i < j and that both
+    /// indices are representable as `NAIDX`.
+    pub fn try_new(i: usize, j: usize) -> Result<Self, StructureError> {
+        if j >= NAIDX::MAX as usize {
+            return Err(StructureError::IndexOverflow(j));
+        }
+        if i >= j {
+            return Err(StructureError::InvalidPair(i, j));
+        }
+        Ok(Pair { i: i as NAIDX, j: j as NAIDX })
+    }
+
     /// Return the 5'-side index.
     pub fn i(&self) -> NAIDX {
         self.i
@@ -46,13 +62,13 @@ impl Pair {
 
     /// Compact 32-bit key encoding both indices.
     pub fn key(&self) -> P1KEY {
-        ((self.i as P1KEY) << 16) | (self.j as P1KEY)
+        ((self.i as P1KEY) << NAIDX::BITS) | (self.j as P1KEY)
     }
 
     /// Decode a key back into a `Pair`.
     pub fn from_key(key: P1KEY) -> Self {
-        let i = (key >> 16) as NAIDX;
-        let j = (key & 0xFFFF) as NAIDX;
+        let i = (key >> NAIDX::BITS) as NAIDX;
+        let j = (key & NAIDX::MAX as P1KEY) as NAIDX;
         debug_assert!(i < j);
         Pair { i, j }
     }
@@ -85,11 +101,25 @@ impl PairSet {
     }
 
     /// Insert a new pair; returns true if it was newly inserted.
+    /// Panics if the pair does not fit the sequence length. Use 
+    /// `try_insert` for indices that are not known to be valid.
     pub fn insert(&mut self, pair: Pair) -> bool {
-        debug_assert!((pair.j() as usize) < self.length);
+        assert!(pair.j() < NAIDX::MAX && (pair.j() as usize) < self.length,
+            "Pair ({}, {}) out of range for length {}", pair.i(), pair.j(), self.length);
         self.pairs.insert(pair.key())
     }
 
+    /// Insert the pair (i, j) with checked indices; returns true if 
+    /// it was newly inserted. This is the entry point for indices from
+    /// user input, as they may not be representable as `NAIDX`.
+    pub fn try_insert(&mut self, i: usize, j: usize) -> Result<bool, StructureError> {
+        let pair = Pair::try_new(i, j)?;
+        if j >= self.length {
+            return Err(StructureError::IndexOverflow(j));
+        }
+        Ok(self.insert(pair))
+    }
+
     /// Check if a pair exists in the set.
     pub fn contains(&self, pair: &Pair) -> bool {
         self.pairs.contains(&pair.key())
@@ -103,14 +133,100 @@ impl PairSet {
     /// Return all pairs as a Vec (for deterministic inspection).
     pub fn to_vec(&self) -> Vec<Pair> {
         let mut v: Vec<_> = self.iter().collect();
-        v.sort_unstable_by_key(|p| (p.i(), p.j()));
+        v.sort_unstable();
         v
     }
 
+    /// True if the set contains two pairs (a, b) and (c, d) with 
+    /// a < c < b < d, i.e. it cannot be represented as a PairTable.
+    pub fn has_crossing(&self) -> bool {
+        let mut stack: Vec<NAIDX> = Vec::new();
+        for pair in self.to_vec() {
+            while stack.last().is_some_and(|&j| j < pair.i()) {
+                stack.pop();
+            }
+            if stack.last().is_some_and(|&j| j < pair.j()) {
+                return true;
+            }
+            stack.push(pair.j());
+        }
+        false
+    }
+
+    /// All crossing pairs ((a, b), (c, d)) with a < c < b < d, 
+    /// sorted by the 5' pair first.
+    pub fn crossing_pairs(&self) -> Vec<(Pair, Pair)> {
+        let mut open: Vec<Pair> = Vec::new();
+        let mut crossings = Vec::new();
+        for pair in self.to_vec() {
+            open.retain(|p| p.j() > pair.i());
+            crossings.extend(open.iter()
+                .filter(|p| p.j() < pair.j())
+                .map(|&p| (p, pair)));
+            open.push(pair);
+        }
+        crossings.sort_unstable();
+        crossings
+    }
+
+    /// A 64-bit fingerprint of the pair set, e.g. for use as a database key.
+    ///
+    /// The fingerprint is the FNV-1a hash of the length followed by all pair
+    /// keys in ascending order (little-endian bytes). It does not depend on
+    /// the iteration order of the underlying set, and is therefore stable 
+    /// across runs and platforms.
+    pub fn fingerprint(&self) -> u64 {
+        const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        let mut keys: Vec<P1KEY> = self.pairs.iter().copied().collect();
+        keys.sort_unstable();
+
+        let mut hash = FNV_OFFSET;
+        let mut feed = |bytes: &[u8]| {
+            for &b in bytes {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+        feed(&(self.length as u64).to_le_bytes());
+        for k in keys {
+            feed(&k.to_le_bytes());
+        }
+        hash
+    }
+
     /// Underlying sequence length (from the originating `PairTable`).
     pub fn length(&self) -> usize {
         self.length
     }
+
+    /// Pairs contained in either set.
+    pub fn union(&self, other: &PairSet) -> PairSet {
+        debug_assert_eq!(self.length, other.length);
+        Self {
+            length: self.length,
+            pairs: self.pairs.union(&other.pairs).copied().collect(),
+        }
+    }
+
+    /// Pairs contained in both sets.
+    pub fn intersection(&self, other: &PairSet) -> PairSet {
+        debug_assert_eq!(self.length, other.length);
+        Self {
+            length: self.length,
+            pairs: self.pairs.intersection(&other.pairs).copied().collect(),
+        }
+    }
+
+    /// Pairs contained in this set, but not in the other.
+    pub fn difference(&self, other: &PairSet) -> PairSet {
+        debug_assert_eq!(self.length, other.length);
+        Self {
+            length: self.length,
+            pairs: self.pairs.difference(&other.pairs).copied().collect(),
+        }
+    }
 }
 
 impl From<&PairTable> for PairSet {
@@ -118,10 +234,8 @@ impl From<&PairTable> for PairSet {
         let mut pairs = IntSet::default();
         for (i, &j_opt) in pt.iter().enumerate() {
             let i = i as NAIDX;
-            if let Some(j) = j_opt {
-                if i < j {
-                    pairs.insert(Pair::new(i, j).key());
-                }
+            if let Some(j) = j_opt && i < j {
+                pairs.insert(Pair::new(i, j).key());
             }
         }
         Self {
@@ -158,6 +272,71 @@ mod tests {
         assert_eq!(p, q);
     }
 
+    #[test]
+    fn test_pair_try_new() {
+        assert_eq!(Pair::try_new(1, 42).unwrap(), Pair::new(1, 42));
+        let max = NAIDX::MAX as usize;
+        assert!(Pair::try_new(0, max - 1).is_ok());
+        assert!(matches!(Pair::try_new(0, max), Err(StructureError::IndexOverflow(j)) if j == max));
+        assert!(matches!(Pair::try_new(0, max + 7), Err(StructureError::IndexOverflow(_))));
+        assert!(matches!(Pair::try_new(5, 5), Err(StructureError::InvalidPair(5, 5))));
+
+        let mut ps = PairSet::new(10);
+        assert!(ps.try_insert(2, 9).unwrap());
+        assert!(!ps.try_insert(2, 9).unwrap());
+        assert!(ps.try_insert(2, 10).is_err());
+        assert!(ps.try_insert(9, 2).is_err());
+        assert_eq!(ps.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range for length 10")]
+    fn test_insert_out_of_range() {
+        PairSet::new(10).insert(Pair::new(2, 10));
+    }
+
+    #[cfg(feature = "wide-index")]
+    #[test]
+    fn test_wide_index() {
+        let n: usize = 100_000;
+        let mut pt = PairTable::try_from(".".repeat(n).as_str()).unwrap();
+        let pairs: [(usize, usize); 3] = [(0, n - 1), (70_000, 99_000), (65_535, 65_600)];
+        for (i, j) in pairs {
+            pt[i] = Some(j as NAIDX);
+            pt[j] = Some(i as NAIDX);
+        }
+        let ps = PairSet::from(&pt);
+        assert_eq!(ps.to_vec(), vec![
+            Pair::new(0, 99_999), Pair::new(65_535, 65_600), Pair::new(70_000, 99_000),
+        ]);
+        let p = Pair::new(70_000, 99_000);
+        assert_eq!(Pair::from_key(p.key()), p);
+    }
+
+    #[test]
+    fn test_crossing_pairs() {
+        let nested = PairSet::from(&PairTable::try_from("((..))").unwrap());
+        assert!(!nested.has_crossing());
+        assert!(nested.crossing_pairs().is_empty());
+
+        // ([)]
+        let mut pk = PairSet::new(4);
+        pk.insert(Pair::new(0, 2));
+        pk.insert(Pair::new(1, 3));
+        assert!(pk.has_crossing());
+        assert_eq!(pk.crossing_pairs(), vec![(Pair::new(0, 2), Pair::new(1, 3))]);
+
+        // (([[..)).]]
+        let mut pk = PairSet::from(&PairTable::try_from("((....))...").unwrap());
+        pk.insert(Pair::new(2, 10));
+        pk.insert(Pair::new(3, 9));
+        assert!(pk.has_crossing());
+        assert_eq!(pk.crossing_pairs(), vec![
+            (Pair::new(0, 7), Pair::new(2, 10)), (Pair::new(0, 7), Pair::new(3, 9)),
+            (Pair::new(1, 6), Pair::new(2, 10)), (Pair::new(1, 6), Pair::new(3, 9)),
+        ]);
+    }
+
     #[test]
     fn test_pair_list_from_pair_table() {
         let pt = PairTable::try_from("((..))").unwrap();
@@ -181,5 +360,63 @@ mod tests {
         assert!(s.contains("(0,5)"));
         assert!(s.contains("(1,4)"));
     }
+
+    #[test]
+    fn test_pair_ordering() {
+        let mut pairs = vec![
+            Pair::new(3, 9), Pair::new(0, 7), Pair::new(3, 4), Pair::new(1, 2), Pair::new(0, 5),
+        ];
+        pairs.sort();
+        assert_eq!(pairs, vec![
+            Pair::new(0, 5), Pair::new(0, 7), Pair::new(1, 2), Pair::new(3, 4), Pair::new(3, 9),
+        ]);
+        assert!(Pair::new(0, 9) < Pair::new(1, 2));
+
+        let sorted: std::collections::BTreeSet<Pair> = pairs.iter().rev().copied().collect();
+        assert!(sorted.iter().eq(pairs.iter()));
+        let hashed: std::collections::HashSet<Pair> = pairs.iter().copied().collect();
+        assert_eq!(hashed.len(), pairs.len());
+        assert!(hashed.contains(&Pair::new(3, 4)));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let a = PairSet::from(&PairTable::try_from("((..))..((...))").unwrap());
+        let b = PairSet::from(&PairTable::try_from("((..))..((...))").unwrap());
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        // Independent of insertion order.
+        let mut c = PairSet::new(15);
+        for p in a.to_vec().into_iter().rev() {
+            c.insert(p);
+        }
+        assert_eq!(a.fingerprint(), c.fingerprint());
+
+        // Fixed value, such that changes to the scheme do not go unnoticed.
+        assert_eq!(PairSet::new(0).fingerprint(), 0xa8c7f832281a39c5);
+
+        let d = PairSet::from(&PairTable::try_from("((..))..(.....)").unwrap());
+        assert_ne!(a.fingerprint(), d.fingerprint());
+        let e = PairSet::from(&PairTable::try_from("((..))..((...)).").unwrap());
+        assert_ne!(a.fingerprint(), e.fingerprint());
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a = PairSet::from(&PairTable::try_from("((..))").unwrap());
+        let b = PairSet::from(&PairTable::try_from("(....)").unwrap());
+
+        let u = a.union(&b);
+        assert_eq!(u.length(), 6);
+        assert_eq!(u.to_vec(), vec![Pair::new(0, 5), Pair::new(1, 4)]);
+
+        let i = a.intersection(&b);
+        assert_eq!(i.length(), 6);
+        assert_eq!(i.to_vec(), vec![Pair::new(0, 5)]);
+
+        let d = a.difference(&b);
+        assert_eq!(d.to_vec(), vec![Pair::new(1, 4)]);
+        assert!(b.difference(&a).is_empty());
+    }
 }
 
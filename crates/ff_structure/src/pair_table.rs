@@ -2,9 +2,13 @@
 
 use std::ops::{Deref, DerefMut, Index, IndexMut};
 use std::convert::TryFrom;
+use rand::Rng;
+use rand::seq::IndexedRandom;
 use crate::NAIDX;
+use crate::Pair;
 use crate::StructureError;
 use crate::{DotBracket, DotBracketVec};
+use crate::{LoopInfo, LoopTable};
 
 /// As of v0.1.3 the PairTable field is private. A pair-table should
 /// be constructed by From or TryFrom traits, but then be save to use.
@@ -27,6 +31,131 @@ impl PairTable {
         }
         true
     }
+
+    /// Check that the table is a valid secondary structure: all partners 
+    /// are in range, no position pairs with itself, every pair is listed
+    /// at both positions, and no two pairs are crossing. This is useful
+    /// when the table was modified directly, e.g. via DerefMut.
+    pub fn validate(&self) -> Result<(), StructureError> {
+        let mut stack = Vec::new();
+        for (i, &p) in self.iter().enumerate() {
+            let Some(j) = p else { continue };
+            let j = j as usize;
+            if j >= self.len() {
+                return Err(StructureError::InvalidToken(
+                    format!("partner {}", j), "pair table".to_string(), i));
+            }
+            if i == j {
+                return Err(StructureError::InvalidPair(i, j));
+            }
+            if self[j] != Some(i as NAIDX) {
+                return Err(if i < j {
+                    StructureError::UnmatchedOpen(i)
+                } else {
+                    StructureError::UnmatchedClose(i)
+                });
+            }
+            if i < j {
+                stack.push(j);
+            } else if stack.pop() != Some(i) {
+                return Err(StructureError::InvalidToken(
+                    format!("crossing pair ({}, {})", j, i), "pair table".to_string(), i));
+            }
+        }
+        Ok(())
+    }
+
+    /// All pairs (i, j) that can be added without creating a pseudoknot,
+    /// i.e. both positions are unpaired and belong to the same loop.
+    ///
+    /// Note: PairTables carry no sequence information, hence there are no
+    /// base-pairing or minimum hairpin size constraints.
+    pub(crate) fn addable_pairs(&self) -> Vec<Pair> {
+        let lt = LoopTable::from(self);
+        let mut pairs = Vec::new();
+        for i in 0..lt.len() {
+            let LoopInfo::Unpaired { l } = lt[i] else { continue };
+            for j in (i + 1)..lt.len() {
+                if lt[j] == (LoopInfo::Unpaired { l }) {
+                    pairs.push(Pair::new(i as NAIDX, j as NAIDX));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Construct a PairTable of length `len` from a list of base-pairs.
+    /// Fails if a pair is out of range, a position is paired twice, or
+    /// if two pairs are crossing (pseudoknot).
+    pub fn try_from_pairs<I>(len: usize, pairs: I) -> Result<Self, StructureError> 
+    where I: IntoIterator<Item = Pair> {
+        let mut table = vec![None; len];
+        for pair in pairs {
+            let (i, j) = (pair.i() as usize, pair.j() as usize);
+            let invalid = |k| StructureError::InvalidToken(
+                format!("pair ({}, {})", i, j), "pair list".to_string(), k);
+            if j >= len {
+                return Err(invalid(j));
+            }
+            for k in [i, j] {
+                if table[k].is_some() {
+                    return Err(invalid(k));
+                }
+            }
+            table[i] = Some(pair.j());
+            table[j] = Some(pair.i());
+        }
+        let mut stack = Vec::new();
+        for (k, &p) in table.iter().enumerate() {
+            match p {
+                Some(l) if (l as usize) > k => stack.push(l as usize),
+                Some(l) if stack.pop() != Some(k) => {
+                    return Err(StructureError::InvalidToken(
+                        format!("crossing pair ({}, {})", l, k), "pair list".to_string(), k));
+                }
+                _ => (),
+            }
+        }
+        Ok(PairTable(table))
+    }
+
+    /// A random structure of length `len`, grown from the open chain by
+    /// legal (non-crossing) pair additions. Before each addition, the
+    /// growth continues with `pair_probability`, and it stops when no 
+    /// more pairs can be added. Panics if `pair_probability` is not in [0, 1].
+    pub fn random<R: Rng>(rng: &mut R, len: usize, pair_probability: f64) -> PairTable {
+        let mut pt = PairTable(vec![None; len]);
+        while rng.random_bool(pair_probability) {
+            let Some(&pair) = pt.addable_pairs().choose(rng) else {
+                break;
+            };
+            pt[pair.i()] = Some(pair.j());
+            pt[pair.j()] = Some(pair.i());
+        }
+        pt
+    }
+
+    /// All structures that differ by a single base-pair: first the
+    /// deletion of every existing pair, then every legal addition
+    /// (see the notes on sequence constraints in `addable_pairs`).
+    pub fn neighbors(&self) -> Vec<PairTable> {
+        let mut result = Vec::new();
+        for (i, &j_opt) in self.iter().enumerate() {
+            if let Some(j) = j_opt && i < j as usize {
+                let mut pt = self.clone();
+                pt[i] = None;
+                pt[j] = None;
+                result.push(pt);
+            }
+        }
+        for pair in self.addable_pairs() {
+            let mut pt = self.clone();
+            pt[pair.i()] = Some(pair.j());
+            pt[pair.j()] = Some(pair.i());
+            result.push(pt);
+        }
+        result
+    }
 }
 
 impl Deref for PairTable {
@@ -88,8 +217,9 @@ impl TryFrom<&str> for PairTable {
                 '(' => stack.push(i),
                 ')' => {
                     let j = stack.pop().ok_or(StructureError::UnmatchedClose(i))?;
-                    table[i] = Some(j as NAIDX);
-                    table[j] = Some(i as NAIDX);
+                    let pair = Pair::try_new(j, i)?;
+                    table[i] = Some(pair.i());
+                    table[j] = Some(pair.j());
                 }
                 '.' => (),
                 _ => return Err(StructureError::InvalidToken(format!("character '{}'", c), "structure".to_string(), i)),
@@ -115,8 +245,9 @@ impl TryFrom<&DotBracketVec> for PairTable {
                 DotBracket::Open => stack.push(i),
                 DotBracket::Close => {
                     let j = stack.pop().ok_or(StructureError::UnmatchedClose(i))?;
-                    table[i] = Some(j as NAIDX);
-                    table[j] = Some(i as NAIDX);
+                    let pair = Pair::try_new(j, i)?;
+                    table[i] = Some(pair.i());
+                    table[j] = Some(pair.j());
                 }
                 DotBracket::Unpaired => {}
                 DotBracket::Break => unreachable!("unexpected Break in single-stranded case"),
@@ -166,6 +297,23 @@ mod tests {
         assert_eq!(format!("{}", err), "Invalid character 'x' in structure at position 1");
     }
 
+    #[test]
+    fn test_validate() {
+        assert!(PairTable::try_from("((..)).(.)").unwrap().validate().is_ok());
+        assert!(PairTable(vec![]).validate().is_ok());
+
+        let pt = PairTable(vec![Some(5), Some(4), None, None, Some(1), None]);
+        assert!(matches!(pt.validate(), Err(StructureError::UnmatchedOpen(0))));
+        let pt = PairTable(vec![None, Some(4), None, None, Some(1), Some(1)]);
+        assert!(matches!(pt.validate(), Err(StructureError::UnmatchedClose(5))));
+        let pt = PairTable(vec![None, Some(1), None]);
+        assert!(matches!(pt.validate(), Err(StructureError::InvalidPair(1, 1))));
+        let pt = PairTable(vec![Some(3), None, Some(0)]);
+        assert!(matches!(pt.validate(), Err(StructureError::InvalidToken(_, _, 0))));
+        let pt = PairTable(vec![Some(2), Some(3), Some(0), Some(1)]);
+        assert!(matches!(pt.validate(), Err(StructureError::InvalidToken(_, _, 2))));
+    }
+
     #[test]
     fn test_well_formed_empty_interval() {
         let pt= PairTable::try_from("...").unwrap();
@@ -192,13 +340,83 @@ mod tests {
         assert!(!pt.is_well_formed(2, 4)); 
     }
 
+    #[test]
+    fn test_neighbors() {
+        let pt = PairTable::try_from("....").unwrap();
+        let nbrs = pt.neighbors();
+        assert_eq!(nbrs.len(), 6);
+        assert!(nbrs.contains(&PairTable::try_from("(..)").unwrap()));
+        assert!(nbrs.contains(&PairTable::try_from("()..").unwrap()));
+
+        let pt = PairTable::try_from("(())").unwrap();
+        assert_eq!(pt.neighbors(), vec![
+            PairTable::try_from(".().").unwrap(),
+            PairTable::try_from("(..)").unwrap(),
+        ]);
+
+        let pt = PairTable::try_from("((..))").unwrap();
+        assert_eq!(pt.neighbors(), vec![
+            PairTable::try_from(".(..).").unwrap(),
+            PairTable::try_from("(....)").unwrap(),
+            PairTable::try_from("((()))").unwrap(),
+        ]);
+
+        // No additions across the helix.
+        let pt = PairTable::try_from(".(..).").unwrap();
+        let nbrs = pt.neighbors();
+        assert_eq!(nbrs.len(), 3);
+        assert!(nbrs.contains(&PairTable::try_from("((..))").unwrap()));
+        assert!(nbrs.contains(&PairTable::try_from(".(()).").unwrap()));
+        assert!(!nbrs.contains(&PairTable::try_from("((.).)").unwrap()));
+    }
+
     #[test]
     #[should_panic(expected = "Invalid interval: j must be <= length")]
     fn test_well_formed_out_of_bounds_assert() {
         let pt = PairTable::try_from("..").unwrap();
         pt.is_well_formed(0, 3); // j = pt.len(), should panic
     }
-}
 
+    #[cfg(not(feature = "wide-index"))]
+    #[test]
+    fn test_index_overflow() {
+        let n = NAIDX::MAX as usize;
+        let s = format!("({})", ".".repeat(n - 2));
+        assert!(PairTable::try_from(s.as_str()).is_ok());
+        let s = format!("({})", ".".repeat(n - 1));
+        assert!(matches!(PairTable::try_from(s.as_str()), Err(StructureError::IndexOverflow(j)) if j == n));
+    }
 
+    #[test]
+    fn test_try_from_pairs() {
+        let pt = PairTable::try_from_pairs(8, [Pair::new(1, 4), Pair::new(0, 7)]).unwrap();
+        assert_eq!(pt, PairTable::try_from("((..)..)").unwrap());
+        assert_eq!(PairTable::try_from_pairs(3, []).unwrap(), PairTable::try_from("...").unwrap());
+
+        assert!(PairTable::try_from_pairs(7, [Pair::new(0, 7)]).is_err());
+        assert!(PairTable::try_from_pairs(8, [Pair::new(0, 7), Pair::new(0, 5)]).is_err());
+        assert!(PairTable::try_from_pairs(8, [Pair::new(0, 4), Pair::new(2, 6)]).is_err());
+    }
 
+    #[test]
+    fn test_random_pair_table() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for len in [0, 1, 2, 7, 20, 50] {
+            for _ in 0..50 {
+                let pt = PairTable::random(&mut rng, len, 0.9);
+                assert_eq!(pt.len(), len);
+                let lt = LoopTable::from(&pt);
+                assert_eq!(lt.len(), len);
+                let dbv = DotBracketVec::from(&pt);
+                assert_eq!(PairTable::try_from(&dbv).unwrap(), pt);
+            }
+        }
+        let open = PairTable::random(&mut rng, 20, 0.0);
+        assert!(open.iter().all(|p| p.is_none()));
+        let full = PairTable::random(&mut rng, 20, 1.0);
+        assert!(full.addable_pairs().is_empty());
+    }
+}
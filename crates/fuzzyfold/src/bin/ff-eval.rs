@@ -56,7 +56,7 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
     init_logging(cli.eval.verbose);
 
-    let model = cli.energy.build_model();
+    let model = cli.energy.build_model()?;
 
     let is_rna = cli.energy.dna.is_none();
     let (header, sequence, structure) = read_eval_input(&cli.eval.input, is_rna)?;
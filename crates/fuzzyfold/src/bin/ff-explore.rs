@@ -83,7 +83,7 @@ fn init_logging(verbosity: u8) {
 fn main() -> Result<()> {
     let cli = Cli::parse();
     init_logging(cli.lmin.verbose);
-    let emodel = Arc::new(cli.energy.build_model());
+    let emodel = Arc::new(cli.energy.build_model()?);
     let is_rna = cli.energy.dna.is_none();
     let (header, sequence, structure) = read_eval_input(&cli.lmin.input, is_rna)?;
     let pairings = PairTable::try_from(&structure)?;
@@ -54,7 +54,7 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
     init_logging(cli.eval.verbose);
 
-    let model = cli.energy.build_model();
+    let model = cli.energy.build_model()?;
 
     let mut lines = stdin().lock().lines();
 
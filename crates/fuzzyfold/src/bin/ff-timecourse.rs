@@ -3,11 +3,10 @@ use std::fs::File;
 use std::io::Write;
 use std::io::BufWriter;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::path::Path;
 use std::path::PathBuf;
 
-use rayon::prelude::*;
-use rand::rng;
 use colored::*;
 use clap::Parser;
 use anyhow::Result;
@@ -16,20 +15,24 @@ use indicatif::ProgressStyle;
 use serde_json::to_string_pretty;
 
 use ff_structure::PairTable;
+use ff_structure::DotBracketVec;
 use ff_energy::EnergyModel;
-use ff_kinetics::RateModel;
-use ff_kinetics::Walker;
-use ff_kinetics::LoopNeighbors;
-use ff_kinetics::shift_policy::*;
-use ff_kinetics::SSA;
+use ff_energy::NucleotideVec;
+use ff_energy::ViennaRNA;
+use ff_kinetics::Event;
+use ff_kinetics::Move;
+use ff_kinetics::Simulation;
 use ff_kinetics::timeline::Timeline;
+use ff_kinetics::timeline::Observable;
 use ff_kinetics::timeline_plotting::plot_occupancy_over_time;
-use ff_kinetics::MacrostateRegistry;
 
-use fuzzyfold::input_parsers::read_eval_input;
+use fuzzyfold::input_parsers::read_eval_records_input;
 use fuzzyfold::energy_parsers::EnergyModelArguments;
 use fuzzyfold::kinetics_parsers::RateModelArguments;
 use fuzzyfold::kinetics_parsers::TimelineParameters;
+use fuzzyfold::kinetics_parsers::parse_observable;
+use fuzzyfold::kinetics_parsers::TemperatureScan;
+use fuzzyfold::kinetics_parsers::parse_temperature_scan;
 
 #[derive(Debug, Parser)]
 #[command(version, about = "Stochastically simulated nucleic acid ensembles over time.")]
@@ -38,6 +41,10 @@ pub struct Cli {
     #[arg(value_name = "INPUT", default_value = "-")]
     input: String,
 
+    /// Simulate the n-th record of the input file (1-based).
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    record: usize,
+
     #[arg(short, long, default_value_t = 1)]
     num_sims: usize,
 
@@ -50,6 +57,37 @@ pub struct Cli {
     #[arg(short, long)]
     title: Option<String>,
 
+    /// Occupancy plot file, .svg or .png (default: <OUTPUT>.svg).
+    #[arg(long, value_name = "FILE")]
+    plot: Option<PathBuf>,
+
+    /// Track an observable over time: pairs, unpaired, or pair:I-J (0-based).
+    #[arg(long, value_name = "NAME", value_parser = parse_observable)]
+    observable: Vec<Observable>,
+
+    /// Write a histogram of the structures at t-end to <OUTPUT>.fin.
+    #[arg(long)]
+    final_states: bool,
+
+    /// Stop a trajectory when it enters this macrostate (may be repeated).
+    #[arg(long, value_name = "NAME")]
+    absorbing: Vec<String>,
+
+    /// Simulate at a ladder of temperatures (start:stop:steps in Celsius),
+    /// and write the final macrostate occupancies to <OUTPUT>.tsc.
+    #[arg(long, value_name = "SCAN", value_parser = parse_temperature_scan,
+        conflicts_with_all = ["observable", "final_states", "plot"])]
+    temp_scan: Option<TemperatureScan>,
+
+    /// Write every move of every trajectory to this CSV file
+    /// (trajectory,time,kind,i,j,delta_e).
+    #[arg(long, value_name = "FILE", conflicts_with = "temp_scan")]
+    events: Option<PathBuf>,
+
+    /// Seed for reproducible runs (trajectory i uses seed + i).
+    #[arg(long, value_name = "U64")]
+    seed: Option<u64>,
+
     #[command(flatten, next_help_heading = "Simulation parameters")]
     simulation: TimelineParameters,
 
@@ -60,29 +98,57 @@ pub struct Cli {
     kinetics: RateModelArguments,
 }
 
+/// Plot files are checked before the simulation, with the same 
+/// (case-insensitive) extensions as the plotting library.
+fn is_plot_format(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref(),
+        Some("svg" | "png"))
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
     cli.simulation.validate()?;
+    if let Some(plot) = &cli.plot && !is_plot_format(plot) {
+        anyhow::bail!("Unsupported plot file '{}' (expected .svg or .png).", plot.display());
+    }
 
     // --- Build simulator ---
-    let emodel = Arc::new(cli.energy.build_model());
-    let rmodel = cli.kinetics.build_model(emodel.temperature());
+    let emodel = Arc::new(cli.energy.build_model()?);
 
     let is_rna = cli.energy.dna.is_none();
-    let (header, sequence, structure) = read_eval_input(&cli.input, is_rna)?;
+    let records = read_eval_records_input(&cli.input, is_rna)?;
+    let num_records = records.len();
+    let Some((header, sequence, structure)) = cli.record.checked_sub(1)
+        .and_then(|n| records.into_iter().nth(n)) else {
+        anyhow::bail!("Record {} not found ({} records in input).", cli.record, num_records);
+    };
     let pairings = PairTable::try_from(&structure)?;
 
     if let Some(h) = header {
         println!("{}", h.yellow());
     } 
     println!("{}", sequence);
+    let energy = emodel.eval_structure(&sequence.to_string(), &pairings);
+    println!("{} {}", structure, format!("{:>6.2}", energy as f64 / 100.0).green());
 
     println!("Output after {} simulations: \n - {:?}\n - {:?}\n - {:?}",
         cli.num_sims, cli.kinetics, cli.simulation, cli.energy);
 
-    let times = cli.simulation.get_output_times();
-    let mut macrostates = MacrostateRegistry::from((sequence.clone(), emodel.clone()));
-    macrostates.insert_files(&cli.macrostates)?;
+    if let Some(scan) = &cli.temp_scan {
+        let tsc_path = cli.output.with_extension("tsc");
+        let mut writer = BufWriter::new(File::create(tsc_path.clone())?);
+        for line in temperature_scan(&cli, &sequence, &pairings, &scan.temperatures())? {
+            println!("{}", line);
+            writeln!(writer, "{}", line)?;
+        }
+        println!("Wrote tsc file: {}", tsc_path.display());
+        return Ok(());
+    }
+
+    let mut simulation = timecourse(&cli, &sequence, &pairings, emodel)
+        .observables(cli.observable.clone())
+        .absorbing(&cli.absorbing);
+    let macrostates = simulation.registry().map_err(anyhow::Error::msg)?;
     // Verbose Output
     println!("{:>4} {:<10} {} {:>5} {:>8}",
         "ID",
@@ -99,55 +165,63 @@ fn main() -> Result<()> {
             m.len(),
             m.ensemble_energy().unwrap());
     }
+    for name in &cli.absorbing {
+        if macrostates.index_of(name).is_none() {
+            anyhow::bail!("Unknown absorbing macrostate '{}'.", name);
+        }
+    }
     let shared_macrostates = Arc::new(macrostates);
 
     let tln_path = cli.output.with_extension("tln");
-    let svg_path = cli.output.with_extension("svg");
+    let plot_path = cli.plot.clone().unwrap_or_else(|| cli.output.with_extension("svg"));
     let nxy_path = cli.output.with_extension("nxy");
+    let obs_path = cli.output.with_extension("obs");
+    let fin_path = cli.output.with_extension("fin");
 
     // If timeline.json exists, reload instead of starting empty
-    let mut master = 
+    let times = cli.simulation.get_output_times();
+    let master = 
         if Path::new(&tln_path).exists() {
+            if !cli.observable.is_empty() {
+                anyhow::bail!("Observables cannot be tracked when extending an existing timeline ({}).",
+                    tln_path.display());
+            }
             println!("Loading existing timeline from: {}", tln_path.display());
             Timeline::from_file(&tln_path, &times, Arc::clone(&shared_macrostates))?
         } else {
             println!("A new timeline file will be created: {}", tln_path.display());
-            Timeline::new(&times, Arc::clone(&shared_macrostates))
+            Timeline::with_observables(&times, Arc::clone(&shared_macrostates), cli.observable.clone())
         };
 
-    let timelines: Vec<_> =
-        match (rmodel.k3ws().is_some(), rmodel.k4ws().is_some()) {
-            (false, false) => {
-                let moves = LoopNeighbors::try_from((sequence.clone(), &pairings, emodel, NoShift))
-                    .map_err(|e| anyhow::anyhow!("failed to construct AddDelMoves: {:?}", e))?;
-                run_timecourse(moves, rmodel, cli.simulation.t_end, cli.num_sims as u64,
-                    Arc::clone(&shared_macrostates), &times).collect()
-            },
-            (true, false) => {
-                let moves = LoopNeighbors::try_from((sequence.clone(), &pairings, emodel, ThreeWayOnly))
-                    .map_err(|e| anyhow::anyhow!("failed to construct AddDelMoves: {:?}", e))?;
-                run_timecourse(moves, rmodel, cli.simulation.t_end, cli.num_sims as u64,
-                    Arc::clone(&shared_macrostates), &times).collect()
-            },
-            (false, true) => {
-                let moves = LoopNeighbors::try_from((sequence.clone(), &pairings, emodel, FourWayOnly))
-                    .map_err(|e| anyhow::anyhow!("failed to construct AddDelMoves: {:?}", e))?;
-                run_timecourse(moves, rmodel, cli.simulation.t_end, cli.num_sims as u64,
-                    Arc::clone(&shared_macrostates), &times).collect()
-            },
-            (true, true) => {
-                let moves = LoopNeighbors::try_from((sequence.clone(), &pairings, emodel, ThreeAndFour))
-                    .map_err(|e| anyhow::anyhow!("failed to construct AddDelMoves: {:?}", e))?;
-                run_timecourse(moves, rmodel, cli.simulation.t_end, cli.num_sims as u64,
-                    Arc::clone(&shared_macrostates), &times).collect()
-            },
-        };
+    let events = match &cli.events {
+        Some(path) => {
+            let writer = Arc::new(Mutex::new(create_events_file(path)?));
+            let sink = Arc::clone(&writer);
+            simulation = simulation.events(move |idx, trajectory| {
+                write_events(&mut *sink.lock().expect("no panics while writing"), idx, trajectory)
+            });
+            Some(writer)
+        },
+        None => None,
+    };
+    let pb = ProgressBar::new(cli.num_sims as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
+        .unwrap()
+        .progress_chars("#>-"),
+    );
+    let progress = pb.clone();
+    simulation = simulation.progress(move || progress.inc(1));
 
-    for timeline in timelines {
-        master.merge(timeline);
-    }
+    let master = simulation.run_into(master).map_err(anyhow::Error::msg)?;
+    pb.finish();
 
     println!("{}", "Finished simulations!".red());
+    if let (Some(events), Some(path)) = (events, &cli.events) {
+        events.lock().expect("no panics while writing").flush()?;
+        println!("Wrote events file: {}", path.display());
+    }
 
     // save / print / plot.
     let mut writer = BufWriter::new(File::create(nxy_path.clone())?);
@@ -159,6 +233,20 @@ fn main() -> Result<()> {
     fs::write(tln_path.clone(), json).unwrap();
     println!("Wrote tln file: {}", tln_path.display());
 
+    if !master.observables.is_empty() {
+        let mut writer = BufWriter::new(File::create(obs_path.clone())?);
+        master.write_observables(&mut writer)?;
+        println!("Wrote obs file: {}", obs_path.display());
+    }
+
+    if cli.final_states {
+        let mut writer = BufWriter::new(File::create(fin_path.clone())?);
+        for (structure, count) in master.final_state_histogram() {
+            writeln!(writer, "{} {:>8}", structure, count)?;
+        }
+        println!("Wrote fin file: {}", fin_path.display());
+    }
+
     let numsim = master.points[0].counter;
     let title = cli.title.unwrap_or({
         format!("ff-timecourse ({} simulations)", 
@@ -179,62 +267,206 @@ fn main() -> Result<()> {
         })
     });
 
-    plot_occupancy_over_time(&master, svg_path.clone(), &title, cli.simulation.t_ext, cli.simulation.t_end);
-    println!("Plotted svg file: {}", svg_path.display());
+    plot_occupancy_over_time(&master, &plot_path, &title, cli.simulation.t_ext, cli.simulation.t_end)?;
+    println!("Plotted file: {}", plot_path.display());
 
     Ok(())
 }
 
 
-fn run_timecourse<W, K, E>(
-    moves: W,
-    rmodel: K,
-    t_end: f64,
-    num_sims: u64,
-    registry: Arc<MacrostateRegistry<E>>,
-    times: &[f64],
-) -> impl ParallelIterator<Item = Timeline<E>>
-where
-    W: Walker + Clone + Send + Sync,
-    K: RateModel + Clone,
-    E: EnergyModel,
-    SSA<W, K>: From<(W, K)>,
-{
-    let pb = ProgressBar::new(num_sims);
-    pb.set_style(
-        ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
-        .unwrap()
-        .progress_chars("#>-"),
-    );
+/// The simulation of the command line arguments, with the rate model at 
+/// the temperature of the energy model.
+fn timecourse(
+    cli: &Cli,
+    sequence: &NucleotideVec,
+    pairings: &PairTable,
+    emodel: Arc<ViennaRNA>,
+) -> Simulation<ViennaRNA> {
+    let simulation = Simulation::new()
+        .sequence(sequence.clone())
+        .structure(DotBracketVec::from(pairings))
+        .rate_model(cli.kinetics.build_model(emodel.temperature()))
+        .energy_model(emodel)
+        .times(&cli.simulation.get_output_times())
+        .num_sims(cli.num_sims)
+        .macrostates(&cli.macrostates);
+    match cli.seed {
+        Some(seed) => simulation.seed(seed),
+        None => simulation,
+    }
+}
+
+/// Run an ensemble at every temperature, with energy and rate model at that
+/// temperature, and return a table of the final macrostate occupancies.
+fn temperature_scan(
+    cli: &Cli,
+    sequence: &NucleotideVec,
+    pairings: &PairTable,
+    temperatures: &[f64],
+) -> Result<Vec<String>> {
+    let mut table = Vec::new();
+    for &celsius in temperatures {
+        let emodel = Arc::new(cli.energy.build_model_at(celsius)?);
+        let timeline = timecourse(cli, sequence, pairings, emodel)
+            .absorbing(&cli.absorbing)
+            .run().map_err(anyhow::Error::msg)?;
+        let registry = &timeline.registry;
+        if table.is_empty() {
+            table.push(format!("{:>8}{}", "celsius",
+                registry.iter().map(|(_, m)| format!(" {:>12}", m.name())).collect::<String>()));
+        }
+
+        let last = timeline.points.last().expect("at least one output time");
+        table.push(format!("{:>8.2}{}", celsius,
+            (0..registry.len()).map(|m| format!(" {:>12.4}", last.occupancy(m))).collect::<String>()));
+    }
+    Ok(table)
+}
 
-    (0..num_sims)
-        .into_par_iter()
-        .map_init(
-            move || pb.clone(), // each thread gets a clone
-            move |pb, _| {
-                let registry = Arc::clone(&registry);
-                let mut timeline = Timeline::new(times, registry);
-
-                let mut simulator = SSA::from((moves.clone(), rmodel.clone()));
-                let mut t_idx = 0;
-                simulator.simulate(
-                    &mut rng(),
-                    t_end,
-                    |t, tinc, _, w| {
-                        while t_idx < times.len() && t + tinc >= times[t_idx] {
-                            let structure = w.current_structure();
-                            timeline.assign_structure(t_idx, &structure);
-                            t_idx += 1;
-                        }
-                        true
-                    },
-                );
-
-                pb.inc(1);
-                timeline
-            },
-        )
+fn create_events_file(path: &Path) -> Result<BufWriter<File>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "trajectory,time,kind,i,j,delta_e")?;
+    Ok(writer)
 }
 
+/// Write the moves of one trajectory as consecutive CSV rows.
+fn write_events<T: Write>(writer: &mut T, trajectory: u64, events: &[Event]) -> std::io::Result<()> {
+    for e in events {
+        let (kind, i, j) = match e.mv {
+            Move::Add { i, j } => ("add", i, j),
+            Move::Del { i, j } => ("del", i, j),
+            Move::ShiftIK { i, j, .. } => ("shift-ik", i, j),
+            Move::ShiftJK { i, j, .. } => ("shift-jk", i, j),
+            Move::ShiftILJK { i, j, .. } => ("shift-iljk", i, j),
+            Move::ShiftIKLJ { i, j, .. } => ("shift-iklj", i, j),
+        };
+        writeln!(writer, "{},{:e},{},{},{},{}", trajectory, e.time, kind, i, j, e.delta_e)?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli(args: &[&str]) -> Cli {
+        let mut argv = vec!["ff-timecourse", "-", "-o", "unused",
+            "--t-ext", "1e-4", "--t-end", "1e-2", "--t-lin", "1", "--t-log", "2"];
+        argv.extend(args);
+        Cli::try_parse_from(argv).unwrap()
+    }
+
+    fn seeded_run(seed: &str) -> String {
+        let sequence = NucleotideVec::try_from("GGGAAACCCAGGGAAAACCCA").unwrap();
+        let pairings = PairTable::try_from(".....................").unwrap();
+        let emodel = Arc::new(ViennaRNA::default());
+        let master = timecourse(&cli(&["-n", "8", "--seed", seed]), &sequence, &pairings, emodel)
+            .run().unwrap();
+        master.final_state_histogram().iter()
+            .map(|(s, c)| format!("{} {}\n", s, c))
+            .collect::<String>() + &format!("{}", master)
+    }
+
+    #[test]
+    fn test_seeded_runs_are_reproducible() {
+        assert_eq!(seeded_run("7"), seeded_run("7"));
+    }
+
+    #[test]
+    fn test_events_csv() {
+        let path = std::env::temp_dir().join("ff_timecourse_events.csv");
+        let sequence = NucleotideVec::try_from("GGGAAACCCAGGGAAAACCCA").unwrap();
+        let pairings = PairTable::try_from(".....................").unwrap();
+        let emodel = Arc::new(ViennaRNA::default());
+
+        let writer = Arc::new(Mutex::new(create_events_file(&path).unwrap()));
+        let sink = Arc::clone(&writer);
+        let timeline = timecourse(&cli(&["-n", "4", "--seed", "1"]), &sequence, &pairings, emodel)
+            .events(move |idx, trajectory| write_events(&mut *sink.lock().unwrap(), idx, trajectory))
+            .run().unwrap();
+        assert_eq!(timeline.n_trajectories(), 4);
+        writer.lock().unwrap().flush().unwrap();
+
+        let csv = fs::read_to_string(&path).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("trajectory,time,kind,i,j,delta_e"));
+        let rows: Vec<(u64, f64)> = lines.map(|l| {
+            let fields: Vec<&str> = l.split(',').collect();
+            assert_eq!(fields.len(), 6);
+            assert!(matches!(fields[2], "add" | "del"));
+            (fields[0].parse().unwrap(), fields[1].parse().unwrap())
+        }).collect();
+        assert!(!rows.is_empty());
+
+        // Rows are grouped by trajectory, and the times increase within each.
+        let mut seen = std::collections::HashSet::new();
+        for chunk in rows.chunk_by(|a, b| a.0 == b.0) {
+            assert!(seen.insert(chunk[0].0));
+            assert!(chunk.windows(2).all(|w| w[0].1 < w[1].1));
+        }
+        assert!(seen.iter().all(|&t| t < 4));
+    }
+
+    #[test]
+    fn test_temperature_scan_melts_hairpin() {
+        let msfile = std::env::temp_dir().join("ff_timecourse_temp_scan.ms");
+        fs::write(&msfile, ">hairpin\nGGGAAACCC\n(((...)))\n").unwrap();
+        let cli = Cli::try_parse_from([
+            "ff-timecourse", "-", "-o", "unused", "-n", "100", "--seed", "3",
+            "--t-ext", "0.01", "--t-end", "0.1", "--t-lin", "1", "--t-log", "1",
+            "--macrostates", msfile.to_str().unwrap(),
+        ]).unwrap();
+        let sequence = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let pairings = PairTable::try_from(".........").unwrap();
+
+        let table = temperature_scan(&cli, &sequence, &pairings, &[20.0, 60.0, 100.0]).unwrap();
+        assert_eq!(table.len(), 4);
+        assert!(table[0].contains("hairpin"));
+        let hairpin: Vec<f64> = table[1..].iter()
+            .map(|l| l.split_whitespace().nth(2).unwrap().parse().unwrap())
+            .collect();
+        assert!(hairpin.windows(2).all(|w| w[0] > w[1]));
+
+        // Trajectories stop in the absorbing hairpin, even where it melts.
+        let cli = Cli::try_parse_from([
+            "ff-timecourse", "-", "-o", "unused", "-n", "100", "--seed", "3",
+            "--t-ext", "0.01", "--t-end", "0.1", "--t-lin", "1", "--t-log", "1",
+            "--macrostates", msfile.to_str().unwrap(), "--absorbing", "hairpin",
+        ]).unwrap();
+        let absorbed = temperature_scan(&cli, &sequence, &pairings, &[100.0]).unwrap();
+        let absorbed: f64 = absorbed[1].split_whitespace().nth(2).unwrap().parse().unwrap();
+        assert!(absorbed > hairpin[2]);
+    }
+
+    #[test]
+    fn test_temperature_scan_conflicts() {
+        let scan = ["ff-timecourse", "-", "-o", "unused", "--temp-scan", "20:60:3", "--absorbing", "a"];
+        assert!(Cli::try_parse_from(scan).is_ok());
+        for flag in [&["--final-states"][..], &["--plot", "x.svg"], &["--observable", "pairs"],
+                &["--events", "x.csv"]] {
+            let mut args = vec!["ff-timecourse", "-", "-o", "unused", "--temp-scan", "20:60:3"];
+            args.extend(flag);
+            assert!(Cli::try_parse_from(args).is_err(), "{:?}", flag);
+        }
+    }
 
+    #[test]
+    fn test_plot_format() {
+        for file in ["fig.svg", "fig.png", "fig.PNG", "out/fig.Svg"] {
+            assert!(is_plot_format(Path::new(file)), "{}", file);
+        }
+        for file in ["fig.pdf", "fig", "svg"] {
+            assert!(!is_plot_format(Path::new(file)), "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_temperature_scan_fitted_parameters() {
+        let cli = Cli::try_parse_from(["ff-timecourse", "-", "-o", "unused", "--rna", "andronescu2007"]).unwrap();
+        let sequence = NucleotideVec::try_from("GGGAAACCC").unwrap();
+        let pairings = PairTable::try_from(".........").unwrap();
+        let err = temperature_scan(&cli, &sequence, &pairings, &[20.0, 37.0]).unwrap_err();
+        assert!(err.to_string().contains("fitted parameters"));
+    }
+}
@@ -55,7 +55,7 @@ pub struct Cli {
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let emodel = Arc::new(cli.energy.build_model());
+    let emodel = Arc::new(cli.energy.build_model()?);
     let rmodel = cli.kinetics.build_model(emodel.temperature());
 
     let is_rna = cli.energy.dna.is_none();
@@ -1,9 +1,13 @@
 use log::debug;
 use colored::*;
+use std::path::PathBuf;
 use clap::Args;
 use clap::ValueEnum;
+use anyhow::Result;
+use anyhow::bail;
 
 use ff_energy::ViennaRNA;
+use ff_energy::DangleMode;
 use ff_energy::parameters::RNA_EXTENDED;
 use ff_energy::parameters::RNA_TURNER_2004;
 use ff_energy::parameters::RNA_ANDRONESCU_2007;
@@ -41,23 +45,67 @@ pub struct EnergyModelArguments {
         value_name = "DNA_PRESET",
         conflicts_with_all = ["rna"])]
     pub dna: Option<Option<DnaParams>>,
+
+    /// ViennaRNA parameter file, overrides the built-in RNA parameters.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["rna", "dna"])]
+    pub params: Option<PathBuf>,
+
+    /// Na+ concentration in mol/l.
+    #[arg(long, default_value = "1.0", value_parser = parse_concentration)]
+    pub na: f64,
+
+    /// Mg2+ concentration in mol/l.
+    #[arg(long, default_value = "0.0", value_parser = parse_concentration)]
+    pub mg: f64,
+
+    /// Dangling end model: 0 (none), 1 (unpaired neighbors), 2 (always).
+    #[arg(long, default_value = "2", value_name = "0|1|2", value_parser = parse_dangles)]
+    pub dangles: DangleMode,
+}
+
+pub fn parse_dangles(spec: &str) -> Result<DangleMode> {
+    Ok(match spec {
+        "0" => DangleMode::None,
+        "1" => DangleMode::Unpaired,
+        "2" => DangleMode::Always,
+        _ => bail!("Invalid dangle mode '{}' (expected 0, 1 or 2)", spec),
+    })
+}
+
+pub fn parse_concentration(spec: &str) -> Result<f64> {
+    let conc: f64 = spec.parse()?;
+    if !(conc.is_finite() && conc >= 0.0) {
+        bail!("Invalid concentration '{}' (expected a non-negative number)", spec);
+    }
+    Ok(conc)
 }
 
 impl EnergyModelArguments {
-    pub fn build_model(&self) -> ViennaRNA {
-        debug!("{} {} °C", "Celsius:".bold().red(), self.celsius);
-        if let Some(rna_choice) = &self.rna {
+    pub fn build_model(&self) -> Result<ViennaRNA> {
+        self.build_model_at(self.celsius)
+    }
+
+    /// Same as `build_model`, but at the given temperature.
+    pub fn build_model_at(&self, celsius: f64) -> Result<ViennaRNA> {
+        debug!("{} {} °C", "Celsius:".bold().red(), celsius);
+        debug!("{} {} M Na+, {} M Mg2+", "Salt:".bold().red(), self.na, self.mg);
+        if self.na == 0.0 && self.mg == 0.0 {
+            bail!("Salt correction requires a non-zero Na+ or Mg2+ concentration!");
+        }
+        let model = if let Some(path) = &self.params {
+            ViennaRNA::from_param_file(path, celsius)?
+        } else if let Some(rna_choice) = &self.rna {
             let preset = rna_choice.unwrap_or(RnaParams::Turner2004ext);
             match preset {
                 RnaParams::Turner2004 => {
-                    ViennaRNA::from_thermo_params(&RNA_TURNER_2004, self.celsius)
+                    ViennaRNA::from_thermo_params(&RNA_TURNER_2004, celsius)
                 },
                 RnaParams::Turner2004ext => {
-                    ViennaRNA::from_thermo_params(&RNA_EXTENDED, self.celsius)
+                    ViennaRNA::from_thermo_params(&RNA_EXTENDED, celsius)
                 },
                 RnaParams::Andronescu2007 => {
-                    if self.celsius != 37.0 {
-                        panic!("Cannot change temperature for fitted parameters!");
+                    if celsius != 37.0 {
+                        bail!("Cannot change temperature for fitted parameters!");
                     }
                     ViennaRNA::from_andrunescu_params(&RNA_ANDRONESCU_2007)
                 },
@@ -66,12 +114,13 @@ impl EnergyModelArguments {
             let preset = dna_choice.unwrap_or(DnaParams::Mathews2004);
             match preset {
                 DnaParams::Mathews2004 => {
-                    ViennaRNA::from_thermo_params(&DNA_MATHEWS_2004, self.celsius)
+                    ViennaRNA::from_thermo_params(&DNA_MATHEWS_2004, celsius)
                 },
             }
         } else {
-            ViennaRNA::from_thermo_params(&RNA_EXTENDED, self.celsius)
-        }
+            ViennaRNA::from_thermo_params(&RNA_EXTENDED, celsius)
+        };
+        Ok(model.with_salt(self.na, self.mg).with_dangles(self.dangles))
     }
 }
 
@@ -121,6 +121,31 @@ fn read_fasta<R: BufRead>(reader: R, _rna: bool
 //  Base parser functions (lenient and strict variants)
 // ============================================================
 
+/// All (strict) records of a file. Records are separated by blank 
+/// lines or by the next header line.
+pub fn read_eval_records<R: BufRead>(reader: R, is_rna: bool) -> Result<Vec<NARecord>> {
+    let mut records = Vec::new();
+    let mut block = String::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        let boundary = line.is_empty() || line.starts_with('>');
+        if boundary && block.lines().any(|l| !l.starts_with('>')) {
+            records.push(read_eval(Cursor::new(&block), is_rna)?);
+            block.clear();
+        }
+        if !line.is_empty() {
+            block.push_str(line);
+            block.push('\n');
+        }
+    }
+    if !block.is_empty() {
+        records.push(read_eval(Cursor::new(&block), is_rna)?);
+    }
+    Ok(records)
+}
+
 pub fn read_cotr<R: BufRead>(reader: R, is_rna: bool) -> Result<(Option<String>, NucleotideVec, DotBracketVec)> {
     parse_na_format(reader, NAMode::Lenient, is_rna)
 }
@@ -181,10 +206,12 @@ macro_rules! define_input_variants {
 //  Apply macro to generate adapters for both variants
 // ============================================================
 
-type NAResult = Result<(Option<String>, NucleotideVec, DotBracketVec)>;
+pub type NARecord = (Option<String>, NucleotideVec, DotBracketVec);
+type NAResult = Result<NARecord>;
 
 define_input_variants!(read_cotr, NAResult);
 define_input_variants!(read_eval, NAResult);
+define_input_variants!(read_eval_records, Result<Vec<NARecord>>);
 define_input_variants!(read_fasta, NAResult);
 
 // ============================================================
@@ -253,5 +280,28 @@ mod tests {
         let err = read_eval_string(missing, true);
         assert!(err.is_err(), "Missing structure line should fail in strict mode");
     }
-}
 
+    #[test]
+    fn test_read_eval_records() {
+        let input = ">first\nACGU\n....\n\n>second\nGGGAAACCC\n(((...)))\n";
+        let records = read_eval_records_string(input, true).unwrap();
+        assert_eq!(records.len(), 2);
+        let (hdr, seq, dbv) = &records[0];
+        assert_eq!(hdr.as_deref(), Some(">first"));
+        assert_eq!(seq.to_string(), "ACGU");
+        assert_eq!(dbv.to_string(), "....");
+        let (hdr, seq, dbv) = &records[1];
+        assert_eq!(hdr.as_deref(), Some(">second"));
+        assert_eq!(seq.to_string(), "GGGAAACCC");
+        assert_eq!(dbv.to_string(), "(((...)))");
+
+        // Without blank line and header.
+        let input = "ACGU\n....\n>second\nGGGAAACCC\n(((...)))";
+        let records = read_eval_records_string(input, true).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, None);
+
+        let missing = ">first\nACGU\n....\n\n>second\nGGGAAACCC\n";
+        assert!(read_eval_records_string(missing, true).is_err());
+    }
+}
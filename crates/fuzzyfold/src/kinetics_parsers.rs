@@ -1,7 +1,12 @@
 use clap::Args;
 use anyhow::bail;
 use anyhow::Result;
+use ff_structure::DotBracket;
+use ff_structure::DotBracketVec;
+use ff_structure::PairTable;
+use ff_structure::NAIDX;
 use ff_kinetics::Arrhenius;
+use ff_kinetics::timeline::Observable;
 
 #[derive(Debug, Args)]
 pub struct RateModelArguments {
@@ -86,4 +91,67 @@ impl TimelineParameters {
     }
 }
 
+/// Parse an observable name given on the command line:
+///  - `pairs`: number of base-pairs,
+///  - `unpaired`: number of unpaired nucleotides,
+///  - `pair:I-J`: 1.0 if the (0-based) pair (I, J) is formed, 0.0 otherwise.
+pub fn parse_observable(name: &str) -> Result<Observable> {
+    let count = |kind: DotBracket| move |s: &DotBracketVec| {
+        s.0.iter().filter(|&&c| c == kind).count() as f64
+    };
+    match name {
+        "pairs" => Ok(Observable::new(name, count(DotBracket::Open))),
+        "unpaired" => Ok(Observable::new(name, count(DotBracket::Unpaired))),
+        _ => {
+            let Some((i, j)) = name.strip_prefix("pair:").and_then(|p| p.split_once('-')) else {
+                bail!("Unknown observable '{}' (expected pairs, unpaired or pair:I-J)", name);
+            };
+            let (i, j): (usize, usize) = (i.parse()?, j.parse()?);
+            if i >= j {
+                bail!("Invalid pair observable '{}': I must be smaller than J", name);
+            }
+            Ok(Observable::new(name, move |s: &DotBracketVec| {
+                match (s.0.get(i), s.0.get(j)) {
+                    (Some(DotBracket::Open), Some(DotBracket::Close)) => {
+                        let pt = PairTable::try_from(s)
+                            .expect("structures in a simulation are well-formed");
+                        if pt[i] == Some(j as NAIDX) { 1.0 } else { 0.0 }
+                    }
+                    _ => 0.0,
+                }
+            }))
+        }
+    }
+}
 
+/// A ladder of temperatures given as `start:stop:steps` (in Celsius).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemperatureScan {
+    pub start: f64,
+    pub stop: f64,
+    pub steps: usize,
+}
+
+impl TemperatureScan {
+    /// The evenly spaced temperatures from start to stop (inclusive).
+    pub fn temperatures(&self) -> Vec<f64> {
+        if self.steps == 1 {
+            return vec![self.start];
+        }
+        let step = (self.stop - self.start) / (self.steps - 1) as f64;
+        (0..self.steps).map(|i| self.start + i as f64 * step).collect()
+    }
+}
+
+/// Parse a temperature scan given on the command line as `start:stop:steps`.
+pub fn parse_temperature_scan(spec: &str) -> Result<TemperatureScan> {
+    let fields: Vec<&str> = spec.split(':').collect();
+    let [start, stop, steps] = fields[..] else {
+        bail!("Invalid temperature scan '{}' (expected start:stop:steps)", spec);
+    };
+    let scan = TemperatureScan { start: start.parse()?, stop: stop.parse()?, steps: steps.parse()? };
+    if scan.steps == 0 {
+        bail!("Invalid temperature scan '{}': steps must be > 0", spec);
+    }
+    Ok(scan)
+}